@@ -11,17 +11,52 @@ use std::io;
 use std::io::BufRead;
 use dawg::binary_tree::Builder as BinaryTreeBuilder;
 use dawg::double_array::Builder as DoubleArrayBuilder;
+use dawg::map::Builder as MapBuilder;
 
 fn main() {
     let args: Vec<_> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} OUTPUT_INDEX_FILE", args[0]);
+    let mut with_values = false;
+    let mut threads = None;
+    let mut i = 1;
+    while i + 1 < args.len() {
+        match args[i].as_str() {
+            "--values" => with_values = true,
+            "--threads" if i + 2 < args.len() => {
+                i += 1;
+                threads = Some(args[i].parse().unwrap_or_else(|e| {
+                    println!("[ERROR] Invalid thread count {:?}: reason={}", args[i], e);
+                    process::exit(1);
+                }));
+            }
+            _ => break,
+        }
+        i += 1;
+    }
+    if i + 1 != args.len() {
+        println!("Usage: {} [--values] [--threads N] OUTPUT_INDEX_FILE", args[0]);
+        println!();
+        println!("  --values: each input line is a tab separated pair of a word and a u32 value");
+        println!("  --threads: the number of threads to build the index with (default: 1)");
         process::exit(1);
     }
 
-    let stdin = io::stdin();
-    let output_file = &args[1];
+    if with_values && threads.is_some() {
+        println!("[ERROR] --threads can not be used with --values");
+        process::exit(1);
+    }
+
+    let output_file = &args[args.len() - 1];
+    if with_values {
+        build_map(output_file);
+    } else {
+        build_index(output_file, threads.unwrap_or(1));
+    }
 
+    println!("DONE");
+}
+
+fn build_index(output_file: &str, threads: usize) {
+    let stdin = io::stdin();
     let mut builder = BinaryTreeBuilder::new();
     for line in stdin.lock().lines() {
         match line {
@@ -37,13 +72,55 @@ fn main() {
             }
         }
     }
-    let trie = DoubleArrayBuilder::new().build(builder.finish());
+    let trie = DoubleArrayBuilder::with_threads(threads).build(builder.finish()).unwrap_or_else(|e| {
+        println!("[ERROR] Can't build dawg index: reason={}", e);
+        process::exit(1);
+    });
     if let Err(e) = trie.save(output_file) {
         println!("[ERROR] Can't save dawg index: path={}, reason={}",
                  output_file,
                  e);
         process::exit(1);
     }
+    println!("fill rate: {:.2}%", trie.fill_rate() * 100.0);
+}
 
-    println!("DONE");
+fn build_map(output_file: &str) {
+    let stdin = io::stdin();
+    let mut builder = MapBuilder::new();
+    for line in stdin.lock().lines() {
+        match line {
+            Err(e) => {
+                println!("[ERROR] Can't read line: reason={}", e);
+                process::exit(1);
+            }
+            Ok(line) => {
+                let (word, value) = match line.rfind('\t') {
+                    None => {
+                        println!("[ERROR] Missing value column: line={:?}", line);
+                        process::exit(1);
+                    }
+                    Some(i) => (&line[..i], &line[i + 1..]),
+                };
+                let value: u32 = value.parse().unwrap_or_else(|e| {
+                    println!("[ERROR] Can't parse a value {:?}: reason={}", value, e);
+                    process::exit(1);
+                });
+                if let Err(e) = builder.insert(word.bytes(), value) {
+                    println!("[ERROR] Can't insert a word {:?}: reason={}", word, e);
+                    process::exit(1);
+                }
+            }
+        }
+    }
+    let map = builder.finish().unwrap_or_else(|e| {
+        println!("[ERROR] Can't build dawg map: reason={}", e);
+        process::exit(1);
+    });
+    if let Err(e) = map.save(output_file) {
+        println!("[ERROR] Can't save dawg map: path={}, reason={}",
+                 output_file,
+                 e);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,60 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+extern crate dawg;
+
+use std::env;
+use std::process;
+use std::io;
+use std::io::BufRead;
+use std::io::BufWriter;
+use dawg::binary_tree::Builder as BinaryTreeBuilder;
+use dawg::double_array::Trie;
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 2 {
+        println!("Usage: {} (INDEX_FILE | --binary-tree)", args[0]);
+        println!();
+        println!("  --binary-tree: draws the binary-tree graph of the words read from standard \
+                  input");
+        process::exit(1);
+    }
+
+    let stdout = io::stdout();
+    let mut w = BufWriter::new(stdout.lock());
+    let result = if args[1] == "--binary-tree" {
+        build_binary_tree().to_dot(&mut w)
+    } else {
+        let index_file = &args[1];
+        let trie = Trie::load(index_file).unwrap_or_else(|e| {
+            println!("[ERROR] Can't load DAWG index: path={}, reason={}",
+                     index_file,
+                     e);
+            process::exit(1);
+        });
+        trie.to_dot(&mut w)
+    };
+    if let Err(e) = result {
+        println!("[ERROR] Can't write a graph to standard output: reason={}", e);
+        process::exit(1);
+    }
+}
+
+fn build_binary_tree() -> dawg::binary_tree::Trie {
+    let stdin = io::stdin();
+    let mut builder = BinaryTreeBuilder::unsorted();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap_or_else(|e| {
+            println!("[ERROR] Can't read line: reason={}", e);
+            process::exit(1);
+        });
+        if let Err(e) = builder.insert(line.bytes()) {
+            println!("[ERROR] Can't insert a word {:?}: reason={}", line, e);
+            process::exit(1);
+        }
+    }
+    builder.finish()
+}
@@ -42,10 +42,8 @@ fn main() {
             }
             _ => {}
         };
-        for (word_id, prefix_len) in trie.search_common_prefix(line.bytes()) {
-            println!("[{}] {}",
-                     word_id,
-                     unsafe { line.slice_unchecked(0, prefix_len) });
+        for (word_id, prefix) in trie.search_common_prefix_str(&line) {
+            println!("[{}] {}", word_id, prefix);
         }
 
         println!("");
@@ -0,0 +1,57 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+extern crate dawg;
+
+use std::env;
+use std::process;
+use std::fs::File;
+use std::io::BufRead;
+use std::io::BufReader;
+use dawg::double_array::Trie;
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 2 && args.len() != 3 {
+        println!("Usage: {} INDEX_FILE [WORD_FILE]", args[0]);
+        println!();
+        println!("  WORD_FILE: the sorted words the index must hold, one per line");
+        process::exit(1);
+    }
+
+    let index_file = &args[1];
+    let trie = Trie::load(index_file).unwrap_or_else(|e| {
+        println!("[ERROR] Can't load DAWG index: path={}, reason={}",
+                 index_file,
+                 e);
+        process::exit(1);
+    });
+    if let Err(e) = trie.verify() {
+        println!("[ERROR] Broken DAWG index: path={}, reason={}", index_file, e);
+        process::exit(1);
+    }
+
+    if let Some(word_file) = args.get(2) {
+        let file = File::open(word_file).unwrap_or_else(|e| {
+            println!("[ERROR] Can't open word file: path={}, reason={}", word_file, e);
+            process::exit(1);
+        });
+        let words = BufReader::new(file).lines().map(|line| {
+            line.unwrap_or_else(|e| {
+                    println!("[ERROR] Can't read line: reason={}", e);
+                    process::exit(1);
+                })
+                .into_bytes()
+        });
+        if let Err(e) = trie.verify_words(words) {
+            println!("[ERROR] DAWG index does not match the words: path={}, reason={}",
+                     index_file,
+                     e);
+            process::exit(1);
+        }
+    }
+
+    println!("OK");
+}
@@ -4,62 +4,79 @@
 // see the LICENSE file at the top-level directory.
 
 use std::mem;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::sync::Arc;
 use std::error::Error;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fmt::Error as FmtError;
 use binary_tree::Node;
 use binary_tree::Trie;
+use binary_tree::register::Register;
 use EOS;
 use Char;
-use Word;
+use IntoWord;
 
 pub struct Builder {
-    memo: Memo,
+    memo: Register,
     root: Node,
+    unsorted: bool,
 }
 
-type Memo = HashMap<Rc<Node>, Rc<Node>>;
-
 impl Builder {
     pub fn new() -> Self {
         Builder {
-            memo: Memo::new(),
+            memo: Register::new(),
             root: Node::new(EOS),
+            unsorted: false,
         }
     }
 
-    pub fn insert(&mut self, word: Word) -> InsertResult {
+    /// Makes a builder which accepts words in any order.
+    ///
+    /// Every inserted word is merged into the minimized graph immediately,
+    /// so this is slower than `new` for sorted input.
+    pub fn unsorted() -> Self {
+        Builder { unsorted: true, ..Builder::new() }
+    }
+
+    pub fn insert<W: IntoWord>(&mut self, word: W) -> InsertResult {
+        let word = word.into_word();
+        if self.unsorted {
+            return self.insert_unsorted(word);
+        }
         let mut root = mem::replace(&mut self.root, Node::new(EOS));
-        try!(self.insert_word(&mut root, word));
-        self.root = root;
-        Ok(())
+        let result = self.insert_word(&mut root, word);
+        self.root = root; // a rejected word leaves the root unchanged
+        result
     }
 
     pub fn finish(mut self) -> Trie {
         let mut root = mem::replace(&mut self.root, Node::new(EOS));
-        self.share_children(&mut root);
+        if !self.unsorted {
+            root.child = root.child.take().map(|c| self.share(c));
+        }
         root.fix();
         Trie::new(root)
     }
 
-    fn insert_word(&mut self, parent: &mut Node, mut word: Word) -> InsertResult {
+    fn insert_word<I: Iterator<Item = Char>>(&mut self,
+                                             parent: &mut Node,
+                                             mut word: I)
+                                             -> InsertResult {
         match word.next() {
             Some(ch) if parent.child.as_ref().map_or(false, |c| c.ch == ch) => {
                 let child = parent.child.as_mut().unwrap();
-                self.insert_word(Rc::get_mut(child).unwrap(), word)
+                self.insert_word(Arc::get_mut(child).unwrap(), word)
             }
             next_ch => self.add_new_child(parent, next_ch, word),
         }
     }
 
-    fn add_new_child(&mut self,
-                     parent: &mut Node,
-                     ch: Option<Char>,
-                     mut word: Word)
-                     -> InsertResult {
+    fn add_new_child<I: Iterator<Item = Char>>(&mut self,
+                                               parent: &mut Node,
+                                               ch: Option<Char>,
+                                               mut word: I)
+                                               -> InsertResult {
         match ch {
             None => {
                 parent.is_terminal = true;
@@ -75,28 +92,39 @@ impl Builder {
                 let mut child = Node::new(ch);
                 try!(self.add_new_child(&mut child, word.next(), word));
                 child.sibling = parent.child.take().map(|c| self.share(c));
-                parent.child = Some(Rc::new(child));
+                parent.child = Some(Arc::new(child));
                 Ok(())
             }
         }
     }
 
-    fn share(&mut self, mut node: Rc<Node>) -> Rc<Node> {
-        if let Some(n) = self.memo.get(&node) {
-            return n.clone();
+    fn insert_unsorted<I: Iterator<Item = Char>>(&mut self, word: I) -> InsertResult {
+        let word = word.collect::<Vec<_>>();
+        if word.contains(&EOS) {
+            return Err(InsertError::Eos);
         }
-        self.share_children(Rc::get_mut(&mut node).unwrap());
-        if let Some(n) = self.memo.get(&node) {
-            return n.clone();
+        if word.is_empty() {
+            self.root.is_terminal = true;
+        } else {
+            self.memo.insert_word(&mut self.root, &word);
         }
-        Rc::get_mut(&mut node).unwrap().fix();
-        self.memo.insert(node.clone(), node.clone());
-        node
+        Ok(())
     }
 
-    fn share_children(&mut self, node: &mut Node) {
-        node.sibling = node.sibling.take().map(|n| self.share(n));
-        node.child = node.child.take().map(|n| self.share(n));
+    // Replaces the head of a finished child list with its shared copy.
+    // The siblings of the head were shared when the head was added, so only
+    // the most recently added descendants (the old active path) are visited.
+    fn share(&mut self, mut node: Arc<Node>) -> Arc<Node> {
+        {
+            let n = Arc::get_mut(&mut node).unwrap();
+            n.child = n.child.take().map(|c| self.share(c));
+            n.fix();
+        }
+        if let Some(n) = self.memo.get(&node) {
+            return n;
+        }
+        self.memo.insert(node.clone());
+        node
     }
 }
 
@@ -6,8 +6,11 @@
 mod builder;
 mod trie;
 mod node;
+mod register;
 
 pub type Builder = builder::Builder;
+pub type InsertError = builder::InsertError;
+pub type InsertResult = builder::InsertResult;
 pub type Trie = trie::Trie;
 pub type Node = node::Node;
 pub type NodeAddr = node::NodeAddr;
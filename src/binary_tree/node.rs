@@ -3,7 +3,7 @@
 // This software is released under the MIT License,
 // see the LICENSE file at the top-level directory.
 
-use std::rc::Rc;
+use std::sync::Arc;
 use std::cmp::PartialEq;
 use std::hash::Hash;
 use std::hash::Hasher;
@@ -14,8 +14,8 @@ use Char;
 pub struct Node {
     pub ch: Char,
     pub is_terminal: bool,
-    pub child: Option<Rc<Node>>,
-    pub sibling: Option<Rc<Node>>,
+    pub child: Option<Arc<Node>>,
+    pub sibling: Option<Arc<Node>>,
     child_total: u32,
     sibling_total: u32,
 }
@@ -79,15 +79,15 @@ impl Node {
 
 #[derive(Clone)]
 pub struct Children {
-    curr: Option<Rc<Node>>,
+    curr: Option<Arc<Node>>,
 }
 
 impl Iterator for Children {
-    type Item = Rc<Node>;
+    type Item = Arc<Node>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.curr.take().map(|mut child| {
-            self.curr = Rc::get_mut(&mut child)
+            self.curr = Arc::get_mut(&mut child)
                             .map(|c| c.sibling.take())
                             .unwrap_or_else(|| child.sibling.clone());
             child
@@ -0,0 +1,192 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::sync::Arc;
+use binary_tree::Node;
+use Char;
+
+// The set of the distinct nodes of a minimized graph.
+// Two nodes are equal if they have the same label and terminal flag and share
+// the same child and sibling, so looking a node up finds its shared copy.
+pub struct Register {
+    memo: HashSet<Arc<Node>>,
+    // The number of the links to each node from the root and the registered nodes.
+    // Only kept by `insert_word` and `remove_word`, which drop a node once it reaches zero.
+    // The reference counts can't tell this, as iterators may still hold the old nodes.
+    links: HashMap<usize, usize>,
+}
+
+impl Register {
+    pub fn new() -> Self {
+        Register {
+            memo: HashSet::new(),
+            links: HashMap::new(),
+        }
+    }
+
+    // Registers all the nodes below `root`
+    pub fn from_root(root: &Node) -> Self {
+        let mut register = Register::new();
+        let mut stack = root.child.iter().cloned().collect::<Vec<_>>();
+        if let Some(ref child) = root.child {
+            register.link(child);
+        }
+        while let Some(node) = stack.pop() {
+            if register.memo.contains(&node) {
+                continue;
+            }
+            for next in node.child.iter().chain(node.sibling.iter()) {
+                register.link(next);
+                stack.push(next.clone());
+            }
+            register.memo.insert(node);
+        }
+        register
+    }
+
+    pub fn get(&self, node: &Node) -> Option<Arc<Node>> {
+        self.memo.get(node).cloned()
+    }
+
+    pub fn insert(&mut self, node: Arc<Node>) {
+        self.memo.insert(node);
+    }
+
+    // Adds `word` below `root`, copying the nodes on its path instead of modifying
+    // shared ones. `word` must not be empty.
+    pub fn insert_word(&mut self, root: &mut Node, word: &[Char]) {
+        let old = root.child.take();
+        let new = self.insert_into(old.clone(), word);
+        self.link(&new);
+        root.child = Some(new);
+        if let Some(old) = old {
+            self.unlink(&old);
+        }
+        root.fix();
+    }
+
+    // Removes `word` from below `root` in the same way as `insert_word`.
+    // Returns false if there is no such word.
+    pub fn remove_word(&mut self, root: &mut Node, word: &[Char]) -> bool {
+        let old = root.child.take();
+        match self.remove_from(&old, word) {
+            None => {
+                root.child = old;
+                false
+            }
+            Some(list) => {
+                if let Some(ref new) = list {
+                    self.link(new);
+                }
+                root.child = list;
+                if let Some(old) = old {
+                    self.unlink(&old);
+                }
+                root.fix();
+                true
+            }
+        }
+    }
+
+    // Returns a shared copy of the sibling list `list` from which `word` is removed,
+    // or None if `word` is not in the list
+    fn remove_from(&mut self, list: &Option<Arc<Node>>, word: &[Char]) -> Option<Option<Arc<Node>>> {
+        let ch = word[0];
+        let n = match *list {
+            Some(ref n) if n.ch >= ch => n,
+            _ => return None,
+        };
+        if n.ch > ch {
+            return self.remove_from(&n.sibling, word).map(|sibling| {
+                let mut node = (**n).clone();
+                node.sibling = sibling;
+                Some(self.intern(node))
+            });
+        }
+
+        let mut node = (**n).clone();
+        if word.len() == 1 {
+            if !node.is_terminal {
+                return None;
+            }
+            node.is_terminal = false;
+        } else {
+            match self.remove_from(&n.child, &word[1..]) {
+                None => return None,
+                Some(child) => node.child = child,
+            }
+        }
+        if node.is_terminal || node.child.is_some() {
+            Some(Some(self.intern(node)))
+        } else {
+            Some(node.sibling) // no word passes through the node any more
+        }
+    }
+
+    // Returns a shared copy of the sibling list `list` to which `word` is added
+    fn insert_into(&mut self, list: Option<Arc<Node>>, word: &[Char]) -> Arc<Node> {
+        let ch = word[0];
+        let mut node = match list {
+            Some(ref n) if n.ch == ch => (**n).clone(),
+            Some(ref n) if n.ch > ch => {
+                let mut node = (**n).clone();
+                node.sibling = Some(self.insert_into(n.sibling.clone(), word));
+                return self.intern(node);
+            }
+            _ => {
+                let mut node = Node::new(ch);
+                node.sibling = list.clone();
+                node
+            }
+        };
+        if word.len() == 1 {
+            node.is_terminal = true;
+        } else {
+            let child = node.child.take();
+            node.child = Some(self.insert_into(child, &word[1..]));
+        }
+        self.intern(node)
+    }
+
+    fn intern(&mut self, mut node: Node) -> Arc<Node> {
+        node.fix();
+        if let Some(n) = self.get(&node) {
+            return n;
+        }
+        for next in node.child.iter().chain(node.sibling.iter()) {
+            self.link(next);
+        }
+        let node = Arc::new(node);
+        self.insert(node.clone());
+        node
+    }
+
+    fn link(&mut self, node: &Arc<Node>) {
+        *self.links.entry(node.addr() as usize).or_insert(0) += 1;
+    }
+
+    // Removes `node` and then its descendants from the register if no link to them is left
+    fn unlink(&mut self, node: &Arc<Node>) {
+        let addr = node.addr() as usize;
+        match self.links.get_mut(&addr) {
+            Some(count) if *count > 1 => {
+                *count -= 1;
+                return;
+            }
+            Some(_) => {}
+            None => return,
+        }
+        self.links.remove(&addr);
+        self.memo.remove(&**node);
+        if let Some(ref child) = node.child {
+            self.unlink(child);
+        }
+        if let Some(ref sibling) = node.sibling {
+            self.unlink(sibling);
+        }
+    }
+}
@@ -3,46 +3,267 @@
 // This software is released under the MIT License,
 // see the LICENSE file at the top-level directory.
 
-use std::rc::Rc;
+use std::collections::HashMap;
+use std::io::Write;
+use std::ops::Range;
+use std::sync::Arc;
 use WordId;
-use Word;
+use IntoWord;
+use Char;
+use EOS;
+use Error;
 use binary_tree::Node;
+use binary_tree::NodeAddr;
+use binary_tree::InsertError;
+use binary_tree::InsertResult;
+use binary_tree::register::Register;
+use common;
+use common::Automaton;
+use common::AutomatonIter;
 use common::CommonPrefixIter;
+use common::FuzzyIter;
+use common::Levenshtein;
+use common::PredictiveIter;
 use common::NodeTraverse;
+use common::Stats;
 
+// A trie is `Send + Sync`, so one instance can be queried from many threads at once
+// through `&Trie`. The nodes are shared by `Arc`, and a query or an iterator holds a
+// reference to the root instead of a copy of it. `insert` and `remove` need `&mut Trie`
+// and copy the nodes they change, so the iterators made before them see the old words.
 pub struct Trie {
-    root: Node,
+    root: Arc<Node>,
+    register: Option<Register>, // made on the first call of `insert` or `remove`
 }
 
 impl Trie {
     pub fn new(root: Node) -> Self {
-        Trie { root: root }
+        Trie {
+            root: Arc::new(root),
+            register: None,
+        }
+    }
+
+    /// Adds a word to the trie in any order.
+    ///
+    /// The nodes on the path of the word are copied rather than modified, so the other
+    /// words sharing them are not affected and the graph stays minimized. The first call
+    /// of `insert` or `remove` walks the whole graph to collect its nodes.
+    pub fn insert<W: IntoWord>(&mut self, word: W) -> InsertResult {
+        let word = word.into_word().collect::<Vec<_>>();
+        if word.contains(&EOS) {
+            return Err(InsertError::Eos);
+        }
+        if word.is_empty() {
+            Arc::make_mut(&mut self.root).is_terminal = true;
+            return Ok(());
+        }
+        let (root, register) = self.root_and_register();
+        register.insert_word(root, &word);
+        Ok(())
+    }
+
+    /// Removes a word from the trie, keeping the graph minimized.
+    ///
+    /// Returns the id the word had and the range of the ids of the following words,
+    /// each of which is decremented by one, or `None` if the trie does not have the word.
+    pub fn remove<W: IntoWord>(&mut self, word: W) -> Option<(WordId, Range<WordId>)> {
+        let word = word.into_word().collect::<Vec<_>>();
+        let id = match self.get_id(&word) {
+            None => return None,
+            Some(id) => id,
+        };
+        let len = self.len() as WordId;
+        if word.is_empty() {
+            Arc::make_mut(&mut self.root).is_terminal = false;
+        } else {
+            let (root, register) = self.root_and_register();
+            register.remove_word(root, &word);
+        }
+        Some((id, id + 1..len))
     }
 
     pub fn len(&self) -> usize {
         self.root.len()
     }
 
+    pub fn stats(&self) -> Stats {
+        // The number of the links to each node
+        let mut links: HashMap<NodeAddr, usize> = HashMap::new();
+        let mut stack = self.root.child.iter().map(|n| &**n).collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            let count = links.entry(node.addr()).or_insert(0);
+            *count += 1;
+            if *count == 1 {
+                stack.extend(node.child.iter().chain(node.sibling.iter()).map(|n| &**n));
+            }
+        }
+        Stats {
+            key_count: self.len(),
+            key_bytes: words_below(&self.root.child, &mut HashMap::new()).1,
+            node_count: links.len(),
+            shared_node_count: links.values().filter(|c| **c > 1).count(),
+            ..Stats::default()
+        }
+    }
+
     pub fn to_node(self) -> Node {
-        self.root
+        Arc::try_unwrap(self.root).unwrap_or_else(|root| (*root).clone())
     }
 
-    pub fn contains(&self, word: Word) -> bool {
+    pub fn contains<W: IntoWord>(&self, word: W) -> bool {
         self.get_id(word).is_some()
     }
 
-    pub fn get_id(&self, word: Word) -> Option<WordId> {
-        let word_len = word.len();
-        self.search_common_prefix(word).find(|m| word_len == m.1).map(|m| m.0)
+    pub fn get_id<W: IntoWord>(&self, word: W) -> Option<WordId> {
+        common::get_id(word, NodeTraverser { node: self.root.clone() })
     }
 
-    pub fn search_common_prefix<'a>(&self, word: Word<'a>) -> CommonPrefixIter<'a, NodeTraverser> {
-        CommonPrefixIter::new(word, NodeTraverser { node: Rc::new(self.root.clone()) })
+    pub fn get_word(&self, id: WordId) -> Option<Vec<Char>> {
+        common::get_word(NodeTraverser { node: self.root.clone() }, id)
+    }
+
+    pub fn search_common_prefix<W: IntoWord>(&self,
+                                             word: W)
+                                             -> CommonPrefixIter<W::Iter, NodeTraverser> {
+        CommonPrefixIter::new(word, NodeTraverser { node: self.root.clone() })
+    }
+
+    pub fn longest_prefix<W: IntoWord>(&self, word: W) -> Option<(WordId, usize)> {
+        self.longest_prefix_with_depth(word).0
+    }
+
+    // The second element is the length of the longest prefix of `word` in the trie,
+    // whether or not a word ends there.
+    pub fn longest_prefix_with_depth<W: IntoWord>(&self,
+                                                  word: W)
+                                                  -> (Option<(WordId, usize)>, usize) {
+        common::longest_prefix(word, NodeTraverser { node: self.root.clone() })
+    }
+
+    pub fn search_predictive<W: IntoWord>(&self, prefix: W) -> PredictiveIter<NodeTraverser> {
+        PredictiveIter::new(prefix, NodeTraverser { node: self.root.clone() })
+    }
+
+    pub fn iter(&self) -> PredictiveIter<NodeTraverser> {
+        self.search_predictive("".bytes())
+    }
+
+    pub fn search_fuzzy<W: IntoWord>(&self,
+                                     word: W,
+                                     max_distance: usize)
+                                     -> FuzzyIter<NodeTraverser> {
+        let root = NodeTraverser { node: self.root.clone() };
+        FuzzyIter::new(Levenshtein::new(word, max_distance, false), root)
+    }
+
+    pub fn search_fuzzy_with_transposition<W: IntoWord>(&self,
+                                                        word: W,
+                                                        max_distance: usize)
+                                                        -> FuzzyIter<NodeTraverser> {
+        let root = NodeTraverser { node: self.root.clone() };
+        FuzzyIter::new(Levenshtein::new(word, max_distance, true), root)
+    }
+
+    pub fn search<A: Automaton>(&self, automaton: A) -> AutomatonIter<A, NodeTraverser> {
+        let root = NodeTraverser { node: self.root.clone() };
+        AutomatonIter::new(automaton, root)
+    }
+
+    pub fn union(&self, other: &Trie) -> Trie {
+        common::union(self.traverser(), other.traverser())
+    }
+
+    pub fn intersection(&self, other: &Trie) -> Trie {
+        common::intersection(self.traverser(), other.traverser())
+    }
+
+    pub fn difference(&self, other: &Trie) -> Trie {
+        common::difference(self.traverser(), other.traverser())
+    }
+
+    pub fn is_subset(&self, other: &Trie) -> bool {
+        common::is_subset(self.traverser(), other.traverser())
+    }
+
+    // Writes the graph in the Graphviz DOT language. Solid edges point to the first child
+    // and dashed ones to the next sibling, and terminal nodes are drawn with double circles.
+    pub fn to_dot<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        try!(writeln!(w, "digraph dawg {{"));
+        try!(writeln!(w, "  node [shape=circle];"));
+        let mut ids: HashMap<NodeAddr, usize> = HashMap::new();
+        ids.insert(self.root.addr(), 0);
+        let mut stack = vec![&self.root];
+        while let Some(node) = stack.pop() {
+            let id = ids[&node.addr()];
+            let label = if id == 0 {
+                "root".to_string()
+            } else {
+                common::dot_label(node.ch)
+            };
+            try!(writeln!(w,
+                          "  n{} [label=\"{}\"{}];",
+                          id,
+                          label,
+                          if node.is_terminal { " shape=doublecircle" } else { "" }));
+
+            let links = node.child
+                            .iter()
+                            .map(|n| (n, "solid"))
+                            .chain(node.sibling.iter().map(|n| (n, "dashed")));
+            for (next, style) in links {
+                let next_id = ids.len();
+                let next_id = *ids.entry(next.addr()).or_insert_with(|| {
+                    stack.push(next);
+                    next_id
+                });
+                try!(writeln!(w, "  n{} -> n{} [style={}];", id, next_id, style));
+            }
+        }
+        try!(writeln!(w, "}}"));
+        Ok(())
+    }
+
+    fn traverser(&self) -> NodeTraverser {
+        NodeTraverser { node: self.root.clone() }
+    }
+
+    fn root_and_register(&mut self) -> (&mut Node, &mut Register) {
+        if self.register.is_none() {
+            self.register = Some(Register::from_root(&self.root));
+        }
+        (Arc::make_mut(&mut self.root), self.register.as_mut().unwrap())
+    }
+}
+
+// Returns the number of the words below the sibling list `list` and the total length of
+// their parts below it
+fn words_below(list: &Option<Arc<Node>>,
+               memo: &mut HashMap<NodeAddr, (usize, usize)>)
+               -> (usize, usize) {
+    let node = match *list {
+        None => return (0, 0),
+        Some(ref node) => node,
+    };
+    if let Some(total) = memo.get(&node.addr()) {
+        return *total;
+    }
+    let (child_words, child_bytes) = words_below(&node.child, memo);
+    let (sibling_words, sibling_bytes) = words_below(&node.sibling, memo);
+    let through = node.is_terminal as usize + child_words;
+    let total = (through + sibling_words, through + child_bytes + sibling_bytes);
+    memo.insert(node.addr(), total);
+    total
+}
+
+impl PartialEq for Trie {
+    fn eq(&self, other: &Trie) -> bool {
+        self.len() == other.len() && common::is_equal(self.traverser(), other.traverser())
     }
 }
 
 pub struct NodeTraverser {
-    node: Rc<Node>,
+    node: Arc<Node>,
 }
 
 impl NodeTraverse for NodeTraverser {
@@ -54,12 +275,28 @@ impl NodeTraverse for NodeTraverser {
         self.node.id_offset()
     }
 
-    fn jump(&mut self, word: &mut Word) -> Option<()> {
-        word.next().and_then(|ch| {
-            self.node
-                .children()
-                .find(|c| c.ch == ch)
-                .map(|c| self.node = c)
-        })
+    fn jump_char(&mut self, ch: Char) -> Option<()> {
+        self.node
+            .children()
+            .find(|c| c.ch == ch)
+            .map(|c| self.node = c)
+    }
+
+    fn ch(&self) -> Char {
+        self.node.ch
+    }
+
+    fn children(&self) -> Vec<Self> {
+        let mut children = self.node
+                               .children()
+                               .map(|c| NodeTraverser { node: c })
+                               .collect::<Vec<_>>();
+        children.reverse();
+        children
+    }
+
+    // The nodes are immutable and can only refer to the ones made before them
+    fn max_depth(&self) -> usize {
+        usize::MAX
     }
 }
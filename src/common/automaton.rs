@@ -0,0 +1,75 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use WordId;
+use Char;
+use common::Automaton;
+use common::NodeTraverse;
+
+// Walks the trie and the automaton in lockstep; a subtree is skipped as soon as
+// the automaton reports that no word under it can match.
+pub struct AutomatonIter<A: Automaton, T> {
+    automaton: A,
+    word: Vec<Char>,
+    stack: Vec<(T, WordId, usize, A::State)>,
+}
+
+impl<A: Automaton, T: NodeTraverse> AutomatonIter<A, T> {
+    pub fn new(automaton: A, root: T) -> Self {
+        let start = automaton.start();
+        let stack = if automaton.can_match(&start) {
+            vec![(root, 0, 0, start)]
+        } else {
+            Vec::new()
+        };
+        AutomatonIter {
+            automaton: automaton,
+            word: Vec::new(),
+            stack: stack,
+        }
+    }
+
+    pub fn automaton(&self) -> &A {
+        &self.automaton
+    }
+
+    // Returns the next matching word together with the state of the automaton after it
+    pub fn next_match(&mut self) -> Option<(WordId, Vec<Char>, A::State)> {
+        while let Some((node, word_id, depth, state)) = self.stack.pop() {
+            if depth != 0 {
+                self.word.truncate(depth - 1);
+                self.word.push(node.ch());
+            }
+
+            let child_id = word_id.wrapping_add(node.is_terminal() as WordId);
+            let children = if depth < node.max_depth() {
+                node.children()
+            } else {
+                Vec::new()
+            };
+            for child in children.into_iter().rev() {
+                let next = self.automaton.step(&state, child.ch());
+                if !self.automaton.can_match(&next) {
+                    continue;
+                }
+                let id = child_id.wrapping_add(child.id_offset());
+                self.stack.push((child, id, depth + 1, next));
+            }
+
+            if node.is_terminal() && self.automaton.is_match(&state) {
+                return Some((word_id, self.word.clone(), state));
+            }
+        }
+        None
+    }
+}
+
+impl<A: Automaton, T: NodeTraverse> Iterator for AutomatonIter<A, T> {
+    type Item = (WordId, Vec<Char>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_match().map(|(id, word, _)| (id, word))
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use std::cmp;
+use WordId;
+use IntoWord;
+use Char;
+use common::Automaton;
+use common::AutomatonIter;
+use common::NodeTraverse;
+use common::utf8::Utf8Pending;
+
+// Accepts the words within `max_distance` edits of the query.
+//
+// A state holds a row of the Levenshtein table, i.e. the distances between the word read
+// so far and every prefix of the query. No continuation can match as soon as all the
+// distances in the row exceed `max_distance`. With `transposition`, swapping two adjacent
+// characters counts as a single edit (the optimal string alignment distance).
+//
+// Characters are bytes, or Unicode scalar values for an automaton made by `new_utf8`.
+pub struct Levenshtein {
+    query: Vec<u32>,
+    max_distance: usize,
+    transposition: bool,
+    utf8: bool,
+}
+
+pub struct LevenshteinState {
+    row: Vec<usize>,
+    prev: Option<(Vec<usize>, u32)>, // the previous row and character, for transpositions
+    pending: Utf8Pending,
+}
+
+impl Levenshtein {
+    pub fn new<W: IntoWord>(query: W, max_distance: usize, transposition: bool) -> Self {
+        Levenshtein {
+            query: query.into_word().map(|ch| ch as u32).collect(),
+            max_distance: max_distance,
+            transposition: transposition,
+            utf8: false,
+        }
+    }
+
+    pub fn new_utf8(query: &str, max_distance: usize, transposition: bool) -> Self {
+        Levenshtein {
+            query: query.chars().map(|ch| ch as u32).collect(),
+            max_distance: max_distance,
+            transposition: transposition,
+            utf8: true,
+        }
+    }
+
+    pub fn distance(&self, state: &LevenshteinState) -> usize {
+        state.row[self.query.len()]
+    }
+}
+
+impl Automaton for Levenshtein {
+    type State = LevenshteinState;
+
+    fn start(&self) -> Self::State {
+        LevenshteinState {
+            row: (0..self.query.len() + 1).collect(),
+            prev: None,
+            pending: Utf8Pending::None,
+        }
+    }
+
+    fn step(&self, state: &Self::State, ch: Char) -> Self::State {
+        if !self.utf8 {
+            return self.step_char(state, ch as u32);
+        }
+        match state.pending.push(ch) {
+            Utf8Pending::Char(ch) => self.step_char(state, ch),
+            pending => {
+                LevenshteinState {
+                    row: state.row.clone(),
+                    prev: state.prev.clone(),
+                    pending: pending,
+                }
+            }
+        }
+    }
+
+    fn is_match(&self, state: &Self::State) -> bool {
+        state.pending == Utf8Pending::None && self.distance(state) <= self.max_distance
+    }
+
+    fn can_match(&self, state: &Self::State) -> bool {
+        state.pending != Utf8Pending::Invalid && state.row.iter().any(|d| *d <= self.max_distance)
+    }
+}
+
+impl Levenshtein {
+    fn step_char(&self, state: &LevenshteinState, ch: u32) -> LevenshteinState {
+        let q = &self.query;
+        let prev = &state.row;
+        let mut row = Vec::with_capacity(prev.len());
+        row.push(prev[0] + 1);
+        for j in 1..prev.len() {
+            let cost = (q[j - 1] != ch) as usize;
+            let mut d = cmp::min(cmp::min(prev[j] + 1, row[j - 1] + 1), prev[j - 1] + cost);
+            if let Some((ref grand, last)) = state.prev {
+                if j > 1 && ch == q[j - 2] && last == q[j - 1] {
+                    d = cmp::min(d, grand[j - 2] + 1);
+                }
+            }
+            row.push(d);
+        }
+        LevenshteinState {
+            row: row,
+            prev: if self.transposition {
+                Some((prev.clone(), ch))
+            } else {
+                None
+            },
+            pending: Utf8Pending::None,
+        }
+    }
+}
+
+// Enumerates the words within `max_distance` edits of the query with their distances
+pub struct FuzzyIter<T> {
+    inner: AutomatonIter<Levenshtein, T>,
+}
+
+impl<T: NodeTraverse> FuzzyIter<T> {
+    pub fn new(automaton: Levenshtein, root: T) -> Self {
+        FuzzyIter { inner: AutomatonIter::new(automaton, root) }
+    }
+}
+
+impl<T: NodeTraverse> Iterator for FuzzyIter<T> {
+    type Item = (WordId, Vec<Char>, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next_match().map(|(id, word, state)| {
+            let distance = self.inner.automaton().distance(&state);
+            (id, word, distance)
+        })
+    }
+}
@@ -4,22 +4,49 @@
 // see the LICENSE file at the top-level directory.
 
 use WordId;
-use Word;
+use IntoWord;
+use Char;
 
-pub struct CommonPrefixIter<'a, T> {
+mod automaton;
+mod fuzzy;
+mod regex;
+mod set;
+mod stats;
+mod utf8;
+mod wildcard;
+
+pub use self::set::union;
+pub use self::set::intersection;
+pub use self::set::difference;
+pub use self::set::is_subset;
+pub use self::set::is_equal;
+
+pub type AutomatonIter<A, T> = automaton::AutomatonIter<A, T>;
+pub type CommonPrefixStrIter<'a, T> = utf8::CommonPrefixStrIter<'a, T>;
+pub type FuzzyIter<T> = fuzzy::FuzzyIter<T>;
+pub type FuzzyStrIter<T> = utf8::FuzzyStrIter<T>;
+pub type Levenshtein = fuzzy::Levenshtein;
+pub type Pattern = wildcard::Pattern;
+pub type PredictiveStrIter<T> = utf8::PredictiveStrIter<T>;
+pub type Regex = regex::Regex;
+pub type RegexIter<T> = AutomatonIter<Regex, T>;
+pub type Stats = stats::Stats;
+pub type WildcardIter<T> = AutomatonIter<Pattern, T>;
+
+pub struct CommonPrefixIter<I, T> {
     word_id: WordId,
-    word_len: usize,
-    word: Word<'a>,
+    prefix_len: usize,
+    word: I,
     node: T,
     finished: bool,
 }
 
-impl<'a, T: NodeTraverse> CommonPrefixIter<'a, T> {
-    pub fn new(word: Word<'a>, root: T) -> Self {
+impl<I: Iterator<Item = Char>, T: NodeTraverse> CommonPrefixIter<I, T> {
+    pub fn new<W: IntoWord<Iter = I>>(word: W, root: T) -> Self {
         let mut it = CommonPrefixIter {
             word_id: 0,
-            word_len: word.len(),
-            word: word,
+            prefix_len: 0,
+            word: word.into_word(),
             node: root,
             finished: false,
         };
@@ -39,28 +66,190 @@ impl<'a, T: NodeTraverse> CommonPrefixIter<'a, T> {
     }
 
     fn next_child(&mut self) -> bool {
-        self.node.jump(&mut self.word).map(|_| self.word_id += self.node.id_offset()).is_some()
+        self.node
+            .jump(&mut self.word)
+            .map(|_| {
+                self.word_id = self.word_id.wrapping_add(self.node.id_offset());
+                self.prefix_len += 1;
+            })
+            .is_some()
     }
 }
 
-impl<'a, T: NodeTraverse> Iterator for CommonPrefixIter<'a, T> {
+impl<I: Iterator<Item = Char>, T: NodeTraverse> Iterator for CommonPrefixIter<I, T> {
     type Item = (WordId, usize);
 
     fn next(&mut self) -> Option<Self::Item> {
         if self.finished {
             None
         } else {
-            let prefix_len = self.word_len - self.word.len();
-            let item = (self.word_id, prefix_len);
-            self.word_id += 1;
+            let item = (self.word_id, self.prefix_len);
+            self.word_id = self.word_id.wrapping_add(1);
             self.go_to_next_common_prefix();
             Some(item)
         }
     }
 }
 
-pub trait NodeTraverse {
+// Returns the id and length of the longest word that prefixes `word`, and how many
+// characters of `word` could be followed in the trie.
+pub fn longest_prefix<W: IntoWord, T: NodeTraverse>(word: W,
+                                                    mut node: T)
+                                                    -> (Option<(WordId, usize)>, usize) {
+    let mut word = word.into_word();
+    let mut word_id: WordId = 0;
+    let mut depth = 0;
+    let mut longest = None;
+    loop {
+        if node.is_terminal() {
+            longest = Some((word_id, depth));
+            word_id = word_id.wrapping_add(1);
+        }
+        if node.jump(&mut word).is_none() {
+            return (longest, depth);
+        }
+        word_id = word_id.wrapping_add(node.id_offset());
+        depth += 1;
+    }
+}
+
+pub fn get_id<W: IntoWord, T: NodeTraverse>(word: W, mut node: T) -> Option<WordId> {
+    let mut word_id: WordId = 0;
+    for ch in word.into_word() {
+        word_id = word_id.wrapping_add(node.is_terminal() as WordId);
+        match node.jump_char(ch) {
+            None => return None,
+            Some(_) => word_id = word_id.wrapping_add(node.id_offset()),
+        }
+    }
+    if node.is_terminal() {
+        Some(word_id)
+    } else {
+        None
+    }
+}
+
+pub fn get_word<T: NodeTraverse>(mut node: T, mut id: WordId) -> Option<Vec<Char>> {
+    let mut word = Vec::new();
+    while word.len() <= node.max_depth() {
+        if node.is_terminal() {
+            if id == 0 {
+                return Some(word);
+            }
+            id -= 1;
+        }
+        match node.children().into_iter().rev().find(|c| c.id_offset() <= id) {
+            None => return None,
+            Some(child) => {
+                id -= child.id_offset();
+                word.push(child.ch());
+                node = child;
+            }
+        }
+    }
+    None
+}
+
+// Returns `ch` escaped for a quoted label in the Graphviz DOT language
+pub fn dot_label(ch: Char) -> String {
+    match ch {
+        b'"' => "\\\"".to_string(),
+        b'\\' => "\\\\".to_string(),
+        0x21..=0x7e => (ch as char).to_string(),
+        _ => format!("\\\\x{:02x}", ch),
+    }
+}
+
+pub struct PredictiveIter<T> {
+    word: Vec<Char>,
+    stack: Vec<(T, WordId, usize)>,
+}
+
+impl<T: NodeTraverse> PredictiveIter<T> {
+    pub fn new<W: IntoWord>(prefix: W, mut root: T) -> Self {
+        let mut it = PredictiveIter {
+            word: Vec::new(),
+            stack: Vec::new(),
+        };
+        let mut word_id: WordId = 0;
+        for ch in prefix.into_word() {
+            word_id = word_id.wrapping_add(root.is_terminal() as WordId);
+            if root.jump_char(ch).is_none() {
+                return it;
+            }
+            word_id = word_id.wrapping_add(root.id_offset());
+            it.word.push(ch);
+        }
+        it.stack.push((root, word_id, 0));
+        it
+    }
+}
+
+impl<T: NodeTraverse> Iterator for PredictiveIter<T> {
+    type Item = (WordId, Vec<Char>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, word_id, depth)) = self.stack.pop() {
+            if depth != 0 {
+                self.word.truncate(depth - 1);
+                self.word.push(node.ch());
+            }
+
+            let child_id = word_id.wrapping_add(node.is_terminal() as WordId);
+            let child_depth = self.word.len() + 1;
+            let children = if child_depth <= node.max_depth() {
+                node.children()
+            } else {
+                Vec::new()
+            };
+            for child in children.into_iter().rev() {
+                let id = child_id.wrapping_add(child.id_offset());
+                self.stack.push((child, id, child_depth));
+            }
+            if node.is_terminal() {
+                return Some((word_id, self.word.clone()));
+            }
+        }
+        None
+    }
+}
+
+pub trait NodeTraverse: Sized {
     fn is_terminal(&self) -> bool;
     fn id_offset(&self) -> u32;
-    fn jump(&mut self, word: &mut Word) -> Option<()>;
+    fn jump_char(&mut self, ch: Char) -> Option<()>;
+    fn jump<I: Iterator<Item = Char>>(&mut self, word: &mut I) -> Option<()> {
+        word.next().and_then(|ch| self.jump_char(ch))
+    }
+    fn ch(&self) -> Char;
+    fn children(&self) -> Vec<Self>; // in ascending order of `ch()`
+
+    // The maximum length of a path from the root. Traversals don't go deeper, so that
+    // a broken trie which has a cycle can't make them endless.
+    fn max_depth(&self) -> usize;
+}
+
+// A state machine that constrains the traversal of a trie (see `AutomatonIter`).
+pub trait Automaton {
+    type State;
+    fn start(&self) -> Self::State;
+    fn step(&self, state: &Self::State, ch: Char) -> Self::State;
+    fn is_match(&self, state: &Self::State) -> bool;
+    fn can_match(&self, state: &Self::State) -> bool; // false if no continuation can match
+}
+
+impl<A: Automaton> Automaton for &A {
+    type State = A::State;
+    fn start(&self) -> Self::State {
+        (**self).start()
+    }
+    fn step(&self, state: &Self::State, ch: Char) -> Self::State {
+        (**self).step(state, ch)
+    }
+    fn is_match(&self, state: &Self::State) -> bool {
+        (**self).is_match(state)
+    }
+    fn can_match(&self, state: &Self::State) -> bool {
+        (**self).can_match(state)
+    }
 }
@@ -0,0 +1,591 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use std::cmp;
+use std::collections::HashMap;
+use Char;
+use Error;
+use common::Automaton;
+
+// Upper bounds that keep the compilation of a hostile pattern from blowing up.
+// `MAX_REPEAT` bounds a single repetition, while nested ones are caught by `MAX_NFA_STATES`.
+// `MAX_DEPTH` bounds the nesting of the syntax tree, which is parsed, compiled and dropped
+// recursively.
+const MAX_REPEAT: u32 = 1000;
+const MAX_DEPTH: usize = 256;
+const MAX_NFA_STATES: usize = 100000;
+const MAX_DFA_STATES: usize = 10000;
+
+const DEAD: u32 = 0;
+
+#[derive(Clone, Copy)]
+struct ByteSet([u64; 4]);
+
+impl ByteSet {
+    fn empty() -> Self {
+        ByteSet([0; 4])
+    }
+
+    fn full() -> Self {
+        ByteSet([!0; 4])
+    }
+
+    fn range(from: u8, to: u8) -> Self {
+        let mut set = ByteSet::empty();
+        for b in from..to {
+            set.insert(b);
+        }
+        set.insert(to);
+        set
+    }
+
+    fn insert(&mut self, b: u8) {
+        self.0[(b >> 6) as usize] |= 1 << (b & 63);
+    }
+
+    fn contains(&self, b: u8) -> bool {
+        self.0[(b >> 6) as usize] & (1 << (b & 63)) != 0
+    }
+
+    fn union(&mut self, other: &ByteSet) {
+        for i in 0..4 {
+            self.0[i] |= other.0[i];
+        }
+    }
+
+    fn negate(&self) -> Self {
+        ByteSet([!self.0[0], !self.0[1], !self.0[2], !self.0[3]])
+    }
+}
+
+enum Ast {
+    Class(ByteSet),
+    Concat(Vec<Ast>),
+    Alt(Vec<Ast>),
+    Repeat(Box<Ast>, u32, Option<u32>),
+}
+
+// A recursive descent parser for the following syntax:
+// - `ab`, `a|b`, `(ab)`, `(?:ab)`
+// - `a*`, `a+`, `a?`, `a{n}`, `a{n,}`, `a{n,m}` (a trailing `?` is accepted and ignored)
+// - `.`, `[a-c]`, `[^a-c]`, `\d`, `\w`, `\s` and their negations `\D`, `\W`, `\S`
+// - `\n`, `\r`, `\t`, `\xHH` and `\` followed by a punctuation character
+// - `^` at the start and `$` at the end (matches are always anchored to the whole word)
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize, // the number of the groups enclosing `pos`
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).cloned()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let b = self.peek();
+        if b.is_some() {
+            self.pos += 1;
+        }
+        b
+    }
+
+    fn eat(&mut self, b: u8) -> bool {
+        if self.peek() == Some(b) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse(&mut self) -> Result<Ast, Error> {
+        let (ast, _) = try!(self.parse_alt());
+        match self.peek() {
+            None => Ok(ast),
+            Some(_) => Err(invalid_pattern("unmatched `)`")),
+        }
+    }
+
+    // The `parse_*` methods return the parsed tree along with its height
+    fn parse_alt(&mut self) -> Result<(Ast, usize), Error> {
+        let (first, mut height) = try!(self.parse_concat());
+        let mut branches = vec![first];
+        while self.eat(b'|') {
+            let (branch, h) = try!(self.parse_concat());
+            branches.push(branch);
+            height = cmp::max(height, h);
+        }
+        if branches.len() == 1 {
+            Ok((branches.pop().unwrap(), height))
+        } else {
+            Ok((Ast::Alt(branches), try!(nest(height))))
+        }
+    }
+
+    fn parse_concat(&mut self) -> Result<(Ast, usize), Error> {
+        let mut items = Vec::new();
+        let mut height = 0;
+        loop {
+            match self.peek() {
+                None | Some(b'|') | Some(b')') => break,
+                Some(b'^') if self.pos == 0 => {
+                    self.pos += 1;
+                }
+                Some(b'$') if self.pos + 1 == self.bytes.len() => {
+                    self.pos += 1;
+                }
+                Some(_) => {
+                    let (atom, h) = try!(self.parse_atom());
+                    let (item, h) = try!(self.parse_repeat(atom, h));
+                    items.push(item);
+                    height = cmp::max(height, h);
+                }
+            }
+        }
+        Ok((Ast::Concat(items), try!(nest(height))))
+    }
+
+    fn parse_repeat(&mut self, atom: Ast, height: usize) -> Result<(Ast, usize), Error> {
+        let mut ast = atom;
+        let mut height = height;
+        loop {
+            let (min, max) = match self.peek() {
+                Some(b'*') => (0, None),
+                Some(b'+') => (1, None),
+                Some(b'?') => (0, Some(1)),
+                Some(b'{') => {
+                    self.pos += 1;
+                    try!(self.parse_bounds())
+                }
+                _ => return Ok((ast, height)),
+            };
+            self.pos += 1;
+            self.eat(b'?');
+            ast = Ast::Repeat(Box::new(ast), min, max);
+            height = try!(nest(height));
+        }
+    }
+
+    // Parses `n}`, `n,}` or `n,m}` leaving the closing brace unconsumed
+    fn parse_bounds(&mut self) -> Result<(u32, Option<u32>), Error> {
+        let min = match try!(self.parse_number()) {
+            None => return Err(invalid_pattern("missing repetition count")),
+            Some(n) => n,
+        };
+        let max = if self.eat(b',') {
+            try!(self.parse_number())
+        } else {
+            Some(min)
+        };
+        if self.peek() != Some(b'}') {
+            return Err(invalid_pattern("unterminated repetition"));
+        }
+        match max {
+            Some(max) if max < min => Err(invalid_pattern("reversed repetition range")),
+            _ => Ok((min, max)),
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Option<u32>, Error> {
+        let mut n: Option<u32> = None;
+        while let Some(b @ b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+            let m = n.unwrap_or(0) * 10 + (b - b'0') as u32;
+            if m > MAX_REPEAT {
+                return Err(Error::Limit(format!("repetition count exceeds {}", MAX_REPEAT)));
+            }
+            n = Some(m);
+        }
+        Ok(n)
+    }
+
+    fn parse_atom(&mut self) -> Result<(Ast, usize), Error> {
+        let class = match self.next().unwrap() {
+            b'(' => {
+                if self.eat(b'?') && !self.eat(b':') {
+                    return Err(invalid_pattern("unsupported group flag"));
+                }
+                // Each group nests at least a concatenation, so this also bounds the height
+                self.depth = try!(nest(self.depth));
+                let ast = try!(self.parse_alt());
+                self.depth -= 1;
+                if !self.eat(b')') {
+                    return Err(invalid_pattern("unmatched `(`"));
+                }
+                return Ok(ast);
+            }
+            b'*' | b'+' | b'?' | b'{' => return Err(invalid_pattern("nothing to repeat")),
+            b'.' => ByteSet::full(),
+            b'[' => try!(self.parse_class()),
+            b'\\' => try!(self.parse_escape()),
+            b => ByteSet::range(b, b),
+        };
+        Ok((Ast::Class(class), 1))
+    }
+
+    fn parse_class(&mut self) -> Result<ByteSet, Error> {
+        let negated = self.eat(b'^');
+        let mut set = ByteSet::empty();
+        let mut first = true;
+        loop {
+            let from = match self.next() {
+                None => return Err(invalid_pattern("unterminated byte class")),
+                Some(b']') if !first => break,
+                Some(b'\\') => {
+                    let escaped = try!(self.parse_escape());
+                    match single_byte(&escaped) {
+                        Some(b) => b,
+                        None => {
+                            set.union(&escaped);
+                            first = false;
+                            continue;
+                        }
+                    }
+                }
+                Some(b) => b,
+            };
+            first = false;
+            let to = match (self.peek(), self.bytes.get(self.pos + 1)) {
+                (Some(b'-'), Some(&b'\\')) => {
+                    self.pos += 2;
+                    match single_byte(&try!(self.parse_escape())) {
+                        Some(b) => b,
+                        None => return Err(invalid_pattern("invalid range in byte class")),
+                    }
+                }
+                (Some(b'-'), Some(&to)) if to != b']' => {
+                    self.pos += 2;
+                    to
+                }
+                _ => from,
+            };
+            if from > to {
+                return Err(invalid_pattern("reversed range in byte class"));
+            }
+            set.union(&ByteSet::range(from, to));
+        }
+        Ok(if negated {
+            set.negate()
+        } else {
+            set
+        })
+    }
+
+    fn parse_escape(&mut self) -> Result<ByteSet, Error> {
+        let digit = ByteSet::range(b'0', b'9');
+        let mut word = digit;
+        word.union(&ByteSet::range(b'a', b'z'));
+        word.union(&ByteSet::range(b'A', b'Z'));
+        word.insert(b'_');
+        let mut space = ByteSet::range(b'\t', b'\r');
+        space.insert(b' ');
+
+        let b = match self.next() {
+            None => return Err(invalid_pattern("trailing backslash")),
+            Some(b) => b,
+        };
+        Ok(match b {
+            b'd' => digit,
+            b'D' => digit.negate(),
+            b'w' => word,
+            b'W' => word.negate(),
+            b's' => space,
+            b'S' => space.negate(),
+            b'n' => ByteSet::range(b'\n', b'\n'),
+            b'r' => ByteSet::range(b'\r', b'\r'),
+            b't' => ByteSet::range(b'\t', b'\t'),
+            b'x' => {
+                let hex = self.bytes.get(self.pos..self.pos + 2).and_then(|h| {
+                    ::std::str::from_utf8(h).ok().and_then(|h| u8::from_str_radix(h, 16).ok())
+                });
+                match hex {
+                    None => return Err(invalid_pattern("invalid hex escape")),
+                    Some(h) => {
+                        self.pos += 2;
+                        ByteSet::range(h, h)
+                    }
+                }
+            }
+            b if b.is_ascii_alphanumeric() => {
+                return Err(invalid_pattern(&format!("unknown escape: \\{}", b as char)))
+            }
+            b => ByteSet::range(b, b),
+        })
+    }
+}
+
+fn single_byte(set: &ByteSet) -> Option<u8> {
+    let mut found = None;
+    for b in 0..256 {
+        if set.contains(b as u8) {
+            if found.is_some() {
+                return None;
+            }
+            found = Some(b as u8);
+        }
+    }
+    found
+}
+
+// Returns the height of a tree whose highest subtree has the height `height`
+fn nest(height: usize) -> Result<usize, Error> {
+    if height >= MAX_DEPTH {
+        return Err(Error::Limit(format!("nesting depth exceeds {}", MAX_DEPTH)));
+    }
+    Ok(height + 1)
+}
+
+fn invalid_pattern(reason: &str) -> Error {
+    Error::Pattern(reason.to_owned())
+}
+
+// Thompson NFA with epsilon transitions
+struct Nfa {
+    eps: Vec<Vec<usize>>,
+    trans: Vec<Vec<(ByteSet, usize)>>,
+}
+
+impl Nfa {
+    fn add_state(&mut self) -> Result<usize, Error> {
+        if self.eps.len() >= MAX_NFA_STATES {
+            return Err(Error::Limit(format!("regex needs more than {} NFA states",
+                                            MAX_NFA_STATES)));
+        }
+        self.eps.push(Vec::new());
+        self.trans.push(Vec::new());
+        Ok(self.eps.len() - 1)
+    }
+
+    // Adds the states matching `ast` after `from` and returns the final one
+    fn compile(&mut self, ast: &Ast, from: usize) -> Result<usize, Error> {
+        match *ast {
+            Ast::Class(ref set) => {
+                let to = try!(self.add_state());
+                self.trans[from].push((*set, to));
+                Ok(to)
+            }
+            Ast::Concat(ref items) => {
+                let mut cur = from;
+                for item in items {
+                    cur = try!(self.compile(item, cur));
+                }
+                Ok(cur)
+            }
+            Ast::Alt(ref branches) => {
+                let end = try!(self.add_state());
+                for branch in branches {
+                    let start = try!(self.add_state());
+                    self.eps[from].push(start);
+                    let last = try!(self.compile(branch, start));
+                    self.eps[last].push(end);
+                }
+                Ok(end)
+            }
+            Ast::Repeat(ref item, min, max) => {
+                let mut cur = from;
+                for _ in 0..min {
+                    cur = try!(self.compile(item, cur));
+                }
+                match max {
+                    None => {
+                        let head = try!(self.add_state());
+                        let body = try!(self.add_state());
+                        self.eps[cur].push(head);
+                        self.eps[head].push(body);
+                        let last = try!(self.compile(item, body));
+                        self.eps[last].push(head);
+                        Ok(head)
+                    }
+                    Some(max) => {
+                        let end = try!(self.add_state());
+                        for _ in min..max {
+                            self.eps[cur].push(end);
+                            cur = try!(self.compile(item, cur));
+                        }
+                        self.eps[cur].push(end);
+                        Ok(end)
+                    }
+                }
+            }
+        }
+    }
+
+    // Extends `states` with the states reachable through epsilon transitions.
+    // `seen` must be all false and is left so.
+    fn closure(&self, states: &mut Vec<usize>, seen: &mut [bool]) {
+        for &s in states.iter() {
+            seen[s] = true;
+        }
+        let mut i = 0;
+        while i < states.len() {
+            for &s in &self.eps[states[i]] {
+                if !seen[s] {
+                    seen[s] = true;
+                    states.push(s);
+                }
+            }
+            i += 1;
+        }
+        for &s in states.iter() {
+            seen[s] = false;
+        }
+        states.sort();
+    }
+
+    // Partitions the bytes into classes that no transition tells apart,
+    // and returns the class of each byte and the number of classes.
+    fn byte_classes(&self) -> (Vec<usize>, usize) {
+        let mut classes = vec![0; 256];
+        let mut count = 1;
+        for set in self.trans.iter().flat_map(|t| t.iter().map(|e| &e.0)) {
+            let mut split = HashMap::new();
+            for (b, class) in classes.iter_mut().enumerate() {
+                let key = (*class, set.contains(b as u8));
+                let next = split.len();
+                *class = *split.entry(key).or_insert(next);
+            }
+            count = split.len();
+        }
+        (classes, count)
+    }
+}
+
+// A byte-level DFA compiled from a regular expression.
+// Only whole words are matched, as if the pattern were enclosed by `^` and `$`.
+pub struct Regex {
+    start: u32,
+    trans: Vec<u32>,
+    accepts: Vec<bool>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let ast = try!(Parser {
+                           bytes: pattern.as_bytes(),
+                           pos: 0,
+                           depth: 0,
+                       }
+                       .parse());
+        let mut nfa = Nfa {
+            eps: Vec::new(),
+            trans: Vec::new(),
+        };
+        let start = try!(nfa.add_state());
+        let last = try!(nfa.compile(&ast, start));
+        let mut regex = try!(Regex::determinize(&nfa, start, last));
+        regex.prune();
+        Ok(regex)
+    }
+
+    fn determinize(nfa: &Nfa, start: usize, last: usize) -> Result<Self, Error> {
+        // the state 0 is the dead state (the empty set of NFA states)
+        let mut sets = vec![Vec::new()];
+        let mut ids = HashMap::new();
+        ids.insert(Vec::new(), DEAD);
+
+        let mut seen = vec![false; nfa.eps.len()];
+        let mut initial = vec![start];
+        nfa.closure(&mut initial, &mut seen);
+        ids.insert(initial.clone(), 1);
+        sets.push(initial);
+
+        let (classes, class_count) = nfa.byte_classes();
+        let mut trans = vec![DEAD; 256];
+        let mut i = 1;
+        while i < sets.len() {
+            let mut class_trans = Vec::with_capacity(class_count);
+            for class in 0..class_count {
+                let b = classes.iter().position(|c| *c == class).unwrap() as u8;
+                let mut next = Vec::new();
+                for &s in &sets[i] {
+                    for &(ref set, to) in &nfa.trans[s] {
+                        if set.contains(b) && !next.contains(&to) {
+                            next.push(to);
+                        }
+                    }
+                }
+                nfa.closure(&mut next, &mut seen);
+                let id = match ids.get(&next) {
+                    Some(id) => *id,
+                    None => {
+                        if sets.len() >= MAX_DFA_STATES {
+                            return Err(Error::Limit(format!("regex needs more than {} states",
+                                                            MAX_DFA_STATES)));
+                        }
+                        sets.len() as u32
+                    }
+                };
+                if id as usize == sets.len() {
+                    ids.insert(next.clone(), id);
+                    sets.push(next);
+                }
+                class_trans.push(id);
+            }
+            trans.extend(classes.iter().map(|c| class_trans[*c]));
+            i += 1;
+        }
+
+        Ok(Regex {
+            start: 1,
+            trans: trans,
+            accepts: sets.iter().map(|set| set.contains(&last)).collect(),
+        })
+    }
+
+    // Redirects the transitions into states that can't reach an accepting state to DEAD,
+    // so a search stops descending as early as possible.
+    fn prune(&mut self) {
+        let n = self.accepts.len();
+        let mut preds = vec![Vec::new(); n];
+        for s in 0..n {
+            for &t in &self.trans[s * 256..(s + 1) * 256] {
+                if preds[t as usize].last() != Some(&s) {
+                    preds[t as usize].push(s);
+                }
+            }
+        }
+
+        // Marks the states from the accepting ones backwards
+        let mut live = self.accepts.clone();
+        let mut queue = (0..n).filter(|s| live[*s]).collect::<Vec<_>>();
+        while let Some(t) = queue.pop() {
+            for &s in &preds[t] {
+                if !live[s] {
+                    live[s] = true;
+                    queue.push(s);
+                }
+            }
+        }
+        for t in self.trans.iter_mut() {
+            if !live[*t as usize] {
+                *t = DEAD;
+            }
+        }
+        if !live[self.start as usize] {
+            self.start = DEAD;
+        }
+    }
+}
+
+impl Automaton for Regex {
+    type State = u32;
+
+    fn start(&self) -> u32 {
+        self.start
+    }
+
+    fn step(&self, state: &u32, ch: Char) -> u32 {
+        self.trans[*state as usize * 256 + ch as usize]
+    }
+
+    fn is_match(&self, state: &u32) -> bool {
+        self.accepts[*state as usize]
+    }
+
+    fn can_match(&self, state: &u32) -> bool {
+        *state != DEAD
+    }
+}
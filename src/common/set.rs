@@ -0,0 +1,122 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use binary_tree::Builder;
+use binary_tree::Trie;
+use Char;
+use common::NodeTraverse;
+
+// Set operations over two tries given by their roots.
+// Both graphs are traversed in lockstep, in lexicographic order, and the resulting words
+// are streamed into a `binary_tree::Builder`.
+
+pub fn union<A: NodeTraverse, B: NodeTraverse>(a: A, b: B) -> Trie {
+    combine(a, b, Op::Union)
+}
+
+pub fn intersection<A: NodeTraverse, B: NodeTraverse>(a: A, b: B) -> Trie {
+    combine(a, b, Op::Intersection)
+}
+
+pub fn difference<A: NodeTraverse, B: NodeTraverse>(a: A, b: B) -> Trie {
+    combine(a, b, Op::Difference)
+}
+
+pub fn is_subset<A: NodeTraverse, B: NodeTraverse>(a: A, b: B) -> bool {
+    let mut stack = vec![(a, Some(b), 0)];
+    while let Some((a, b, depth)) = stack.pop() {
+        if a.is_terminal() && !b.as_ref().map_or(false, |b| b.is_terminal()) {
+            return false;
+        }
+        let pairs = pair_children(Some(&a), b.as_ref(), depth);
+        stack.extend(pairs.into_iter().filter_map(|(_, a, b)| a.map(|a| (a, b, depth + 1))));
+    }
+    true
+}
+
+pub fn is_equal<A: NodeTraverse, B: NodeTraverse>(a: A, b: B) -> bool {
+    let mut stack = vec![(a, b, 0)];
+    while let Some((a, b, depth)) = stack.pop() {
+        if a.is_terminal() != b.is_terminal() {
+            return false;
+        }
+        for (_, a, b) in pair_children(Some(&a), Some(&b), depth) {
+            match (a, b) {
+                (Some(a), Some(b)) => stack.push((a, b, depth + 1)),
+                _ => return false,
+            }
+        }
+    }
+    true
+}
+
+#[derive(Clone, Copy)]
+enum Op {
+    Union,
+    Intersection,
+    Difference,
+}
+
+fn combine<A: NodeTraverse, B: NodeTraverse>(a: A, b: B, op: Op) -> Trie {
+    let mut builder = Builder::new();
+    let mut word = Vec::new();
+    let mut stack = vec![(0, Some(a), Some(b), 0)];
+    while let Some((ch, a, b, depth)) = stack.pop() {
+        if depth != 0 {
+            word.truncate(depth - 1);
+            word.push(ch);
+        }
+
+        let in_a = a.as_ref().map_or(false, |a| a.is_terminal());
+        let in_b = b.as_ref().map_or(false, |b| b.is_terminal());
+        let is_terminal = match op {
+            Op::Union => in_a || in_b,
+            Op::Intersection => in_a && in_b,
+            Op::Difference => in_a && !in_b,
+        };
+        if is_terminal {
+            // words come in sorted order and never contain EOS
+            builder.insert(&word).unwrap();
+        }
+
+        for (ch, a, b) in pair_children(a.as_ref(), b.as_ref(), depth).into_iter().rev() {
+            let keep = match op {
+                Op::Union => true,
+                Op::Intersection => a.is_some() && b.is_some(),
+                Op::Difference => a.is_some(),
+            };
+            if keep {
+                stack.push((ch, a, b, depth + 1));
+            }
+        }
+    }
+    builder.finish()
+}
+
+// Merges the children of `a` and `b` into the ascending list of their labels
+// Pairs the children of the nodes at `depth` by their characters.
+// A node at its trie's maximum depth is treated as having no children.
+fn pair_children<A: NodeTraverse, B: NodeTraverse>(a: Option<&A>,
+                                                   b: Option<&B>,
+                                                   depth: usize)
+                                                   -> Vec<(Char, Option<A>, Option<B>)> {
+    let a = a.filter(|a| depth < a.max_depth());
+    let b = b.filter(|b| depth < b.max_depth());
+    let mut a = a.map_or(Vec::new(), |a| a.children()).into_iter().peekable();
+    let mut b = b.map_or(Vec::new(), |b| b.children()).into_iter().peekable();
+    let mut pairs = Vec::new();
+    loop {
+        let a_ch = a.peek().map(|a| a.ch());
+        let b_ch = b.peek().map(|b| b.ch());
+        let pair = match (a_ch, b_ch) {
+            (None, None) => return pairs,
+            (Some(x), Some(y)) if x == y => (x, a.next(), b.next()),
+            (Some(x), Some(y)) if x < y => (x, a.next(), None),
+            (Some(x), None) => (x, a.next(), None),
+            (_, Some(y)) => (y, None, b.next()),
+        };
+        pairs.push(pair);
+    }
+}
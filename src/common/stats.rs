@@ -0,0 +1,50 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use std::fmt;
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+// Statistics of the structure of a trie.
+// The fields about the double-array layout are zero for a `binary_tree::Trie`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    pub key_count: usize,
+    pub key_bytes: usize, // the total length of the keys
+    pub node_count: usize, // the distinct nodes other than the root, including encoded children
+    pub shared_node_count: usize, // the nodes which have more than one parent
+    pub slot_count: usize,
+    pub used_slot_count: usize,
+    pub node_types: [usize; 4], // the number of the nodes in slots encoded as each type
+    pub ext_count: usize,
+    pub encoded_child_count: usize,
+}
+
+impl Stats {
+    pub fn fill_rate(&self) -> f64 {
+        if self.slot_count == 0 {
+            0.0
+        } else {
+            self.used_slot_count as f64 / self.slot_count as f64
+        }
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        try!(writeln!(f, "key count:           {}", self.key_count));
+        try!(writeln!(f, "key bytes:           {}", self.key_bytes));
+        try!(writeln!(f, "node count:          {}", self.node_count));
+        try!(writeln!(f, "shared node count:   {}", self.shared_node_count));
+        try!(writeln!(f, "slot count:          {}", self.slot_count));
+        try!(writeln!(f, "used slot count:     {}", self.used_slot_count));
+        try!(writeln!(f, "fill rate:           {:.2}%", self.fill_rate() * 100.0));
+        for (i, count) in self.node_types.iter().enumerate() {
+            try!(writeln!(f, "type {} node count:   {}", i, count));
+        }
+        try!(writeln!(f, "ext count:           {}", self.ext_count));
+        write!(f, "encoded child count: {}", self.encoded_child_count)
+    }
+}
@@ -0,0 +1,139 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use std::str::Bytes;
+use WordId;
+use IntoWord;
+use Char;
+use common::CommonPrefixIter;
+use common::FuzzyIter;
+use common::NodeTraverse;
+use common::PredictiveIter;
+
+// Incremental UTF-8 decoding of the bytes on a path of a trie
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Utf8Pending {
+    None,
+    Partial {
+        code: u32,
+        remaining: u8,
+        min: u32, // the smallest code point of this length, to reject overlong forms
+    },
+    Char(u32),
+    Invalid,
+}
+
+impl Utf8Pending {
+    pub fn push(self, b: Char) -> Self {
+        match self {
+            Utf8Pending::None | Utf8Pending::Char(_) => {
+                match b {
+                    0x00..=0x7f => Utf8Pending::Char(b as u32),
+                    0xc0..=0xdf => Utf8Pending::partial((b & 0x1f) as u32, 1, 0x80),
+                    0xe0..=0xef => Utf8Pending::partial((b & 0x0f) as u32, 2, 0x800),
+                    0xf0..=0xf7 => Utf8Pending::partial((b & 0x07) as u32, 3, 0x10000),
+                    _ => Utf8Pending::Invalid,
+                }
+            }
+            Utf8Pending::Partial { code, remaining, min } => {
+                if b & 0xc0 != 0x80 {
+                    return Utf8Pending::Invalid;
+                }
+                let code = (code << 6) | (b & 0x3f) as u32;
+                if remaining > 1 {
+                    return Utf8Pending::partial(code, remaining - 1, min);
+                }
+                match ::std::char::from_u32(code) {
+                    Some(_) if code >= min => Utf8Pending::Char(code),
+                    _ => Utf8Pending::Invalid,
+                }
+            }
+            Utf8Pending::Invalid => Utf8Pending::Invalid,
+        }
+    }
+
+    fn partial(code: u32, remaining: u8, min: u32) -> Self {
+        Utf8Pending::Partial {
+            code: code,
+            remaining: remaining,
+            min: min,
+        }
+    }
+}
+
+// Yields the words that prefix the text, skipping those that end inside a character
+pub struct CommonPrefixStrIter<'a, T> {
+    text: &'a str,
+    inner: CommonPrefixIter<<Bytes<'a> as IntoWord>::Iter, T>,
+}
+
+impl<'a, T: NodeTraverse> CommonPrefixStrIter<'a, T> {
+    pub fn new(text: &'a str, root: T) -> Self {
+        CommonPrefixStrIter {
+            text: text,
+            inner: CommonPrefixIter::new(text.bytes(), root),
+        }
+    }
+}
+
+impl<'a, T: NodeTraverse> Iterator for CommonPrefixStrIter<'a, T> {
+    type Item = (WordId, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let text = self.text;
+        self.inner
+            .by_ref()
+            .find(|m| text.is_char_boundary(m.1))
+            .map(|(id, len)| (id, &text[..len]))
+    }
+}
+
+// Yields the words that start with the prefix, skipping those that are not valid UTF-8
+pub struct PredictiveStrIter<T> {
+    inner: PredictiveIter<T>,
+}
+
+impl<T: NodeTraverse> PredictiveStrIter<T> {
+    pub fn new(prefix: &str, root: T) -> Self {
+        PredictiveStrIter { inner: PredictiveIter::new(prefix.bytes(), root) }
+    }
+}
+
+impl<T: NodeTraverse> Iterator for PredictiveStrIter<T> {
+    type Item = (WordId, String);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (id, word) in self.inner.by_ref() {
+            if let Ok(word) = String::from_utf8(word) {
+                return Some((id, word));
+            }
+        }
+        None
+    }
+}
+
+// Yields the words within the given number of edits counted in Unicode scalar values
+pub struct FuzzyStrIter<T> {
+    inner: FuzzyIter<T>,
+}
+
+impl<T: NodeTraverse> FuzzyStrIter<T> {
+    pub fn new(inner: FuzzyIter<T>) -> Self {
+        FuzzyStrIter { inner: inner }
+    }
+}
+
+impl<T: NodeTraverse> Iterator for FuzzyStrIter<T> {
+    type Item = (WordId, String, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for (id, word, distance) in self.inner.by_ref() {
+            if let Ok(word) = String::from_utf8(word) {
+                return Some((id, word, distance));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use Char;
+use Error;
+use common::Automaton;
+
+// Pattern syntax:
+// - `?`: any single byte
+// - `*`: any (possibly empty) sequence of bytes
+// - `[a-cx]`: a byte in the class (`[!...]` or `[^...]` negates it)
+// - `\c`: the byte `c` itself
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Byte(Char),
+    Any,
+    Star,
+    Class(Vec<(Char, Char)>, bool),
+}
+
+impl Token {
+    fn matches(&self, ch: Char) -> bool {
+        match *self {
+            Token::Byte(b) => b == ch,
+            Token::Any | Token::Star => true,
+            Token::Class(ref ranges, negated) => {
+                ranges.iter().any(|r| r.0 <= ch && ch <= r.1) != negated
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    pub fn parse(pattern: &str) -> Result<Self, Error> {
+        let bytes = pattern.as_bytes();
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < bytes.len() {
+            let token = match bytes[i] {
+                b'?' => Token::Any,
+                b'*' => Token::Star,
+                b'\\' => {
+                    i += 1;
+                    match bytes.get(i) {
+                        None => return Err(invalid_pattern("trailing backslash")),
+                        Some(b) => Token::Byte(*b),
+                    }
+                }
+                b'[' => {
+                    let (token, end) = try!(parse_class(bytes, i + 1));
+                    i = end;
+                    token
+                }
+                b => Token::Byte(b),
+            };
+            // consecutive stars are equivalent to a single one
+            if !(token == Token::Star && tokens.last() == Some(&Token::Star)) {
+                tokens.push(token);
+            }
+            i += 1;
+        }
+        Ok(Pattern { tokens: tokens })
+    }
+
+    fn closure(&self, states: &mut Vec<usize>) {
+        let mut i = 0;
+        while i < states.len() {
+            let s = states[i];
+            if self.tokens.get(s) == Some(&Token::Star) && !states.contains(&(s + 1)) {
+                states.push(s + 1);
+            }
+            i += 1;
+        }
+        states.sort();
+    }
+}
+
+// Returns the class token and the index of the closing `]`
+fn parse_class(bytes: &[u8], start: usize) -> Result<(Token, usize), Error> {
+    let mut i = start;
+    let negated = match bytes.get(i) {
+        Some(&b'!') | Some(&b'^') => {
+            i += 1;
+            true
+        }
+        _ => false,
+    };
+    let mut ranges = Vec::new();
+    loop {
+        let from = match bytes.get(i) {
+            None => return Err(invalid_pattern("unterminated byte class")),
+            Some(&b']') => break,
+            Some(&b'\\') => {
+                i += 1;
+                match bytes.get(i) {
+                    None => return Err(invalid_pattern("trailing backslash")),
+                    Some(b) => *b,
+                }
+            }
+            Some(b) => *b,
+        };
+        let to = match (bytes.get(i + 1), bytes.get(i + 2)) {
+            (Some(&b'-'), Some(&to)) if to != b']' => {
+                i += 2;
+                to
+            }
+            _ => from,
+        };
+        if from > to {
+            return Err(invalid_pattern(&format!("reversed range in byte class: {}-{}",
+                                                from as char,
+                                                to as char)));
+        }
+        ranges.push((from, to));
+        i += 1;
+    }
+    if ranges.is_empty() {
+        return Err(invalid_pattern("empty byte class"));
+    }
+    Ok((Token::Class(ranges, negated), i))
+}
+
+fn invalid_pattern(reason: &str) -> Error {
+    Error::Pattern(reason.to_owned())
+}
+
+impl Automaton for Pattern {
+    type State = Vec<usize>;
+
+    fn start(&self) -> Self::State {
+        let mut states = vec![0];
+        self.closure(&mut states);
+        states
+    }
+
+    fn step(&self, states: &Self::State, ch: Char) -> Self::State {
+        let mut next = Vec::new();
+        for &s in states {
+            match self.tokens.get(s) {
+                Some(t) if t.matches(ch) => {
+                    let to = if *t == Token::Star { s } else { s + 1 };
+                    if !next.contains(&to) {
+                        next.push(to);
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.closure(&mut next);
+        next
+    }
+
+    fn is_match(&self, states: &Self::State) -> bool {
+        states.last() == Some(&self.tokens.len())
+    }
+
+    fn can_match(&self, states: &Self::State) -> bool {
+        !states.is_empty()
+    }
+}
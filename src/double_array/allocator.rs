@@ -3,84 +3,203 @@
 // This software is released under the MIT License,
 // see the LICENSE file at the top-level directory.
 
+use std::cmp;
 use bit_vec::BitVec;
 
+const BLOCK_SIZE: usize = 0x100;
+
+// Only the free slots in the last `OPEN_BLOCKS` blocks are candidates for new nodes
+const OPEN_BLOCKS: usize = 16;
+
+// A block is closed once this many candidate bases in it have been rejected
+const MAX_BLOCK_RETRIES: u32 = 0x1000;
+
+const WINDOW_SIZE: usize = OPEN_BLOCKS * BLOCK_SIZE;
+
+// Finds bases for sibling sets in a double-array which grows by blocks of `BLOCK_SIZE` slots.
+//
+// The free slots of the open blocks form a doubly linked list in ascending order, which is
+// kept for a window of the latest `OPEN_BLOCKS` blocks only. A block is closed, and its free
+// slots are left empty for good, when it leaves the window or when too many candidates in it
+// have failed. So each allocation tries a bounded number of candidates however large the
+// array grows.
 pub struct Allocator {
-    head: usize,
+    head: Option<usize>,
+    links: Vec<Link>, // indexed by `slot % WINDOW_SIZE`
+    blocks: Vec<Block>,
+    first_open_block: usize,
     base_used: BitVec,
     node_used: BitVec,
 }
 
+#[derive(Clone, Copy)]
+struct Link {
+    prev: usize,
+    next: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Block {
+    retries: u32,
+    is_closed: bool,
+}
+
 impl Allocator {
     pub fn new() -> Self {
         Allocator {
-            head: 0x0,
-            base_used: BitVec::from_elem(1, true),
-            node_used: BitVec::from_elem(1, true),
+            head: None,
+            links: vec![Link { prev: 0, next: 0 }; WINDOW_SIZE],
+            blocks: Vec::new(),
+            first_open_block: 0,
+            base_used: BitVec::new(),
+            node_used: BitVec::new(),
         }
     }
 
-    pub fn allocate(&mut self, arcs: &[u8], nexts: &mut Vec<u64>) -> u32 {
-        assert!(arcs.len() > 0);
+    // Returns a base such that `base + arc` is free for every arc and grows `nodes` to cover
+    // all of the slots reachable from the base
+    pub fn allocate(&mut self, arcs: &[u8], nodes: &mut Vec<u64>) -> u32 {
+        assert!(!arcs.is_empty());
 
-        let front = arcs[0];
-        while get_next(self.head, nexts) < front as u32 {
-            self.head = get_next(self.head, nexts) as usize;
-        }
-        let mut prev = self.head;
-        let mut curr = get_next(prev, nexts);
-
-        loop {
-            let base = curr - front as u32;
-            if self.can_allocate(base as usize, &arcs[1..]) {
-                self.allocate_impl(base as usize, &arcs, prev, nexts);
-                return base;
-            }
-            prev = curr as usize;
-            curr = get_next(curr as usize, nexts);
-        }
-    }
-
-    fn allocate_impl(&mut self, base: usize, arcs: &[u8], mut prev: usize, nexts: &mut Vec<u64>) {
-        self.extend_if_needed(base + 0x100, nexts);
+        let base = self.find_base(arcs);
+        self.extend(base + BLOCK_SIZE + 1);
         self.base_used.set(base, true);
         for arc in arcs.iter() {
             let index = base + *arc as usize;
+            if self.is_free(index) {
+                self.unlink(index);
+            }
             self.node_used.set(index, true);
+        }
 
-            while nexts[prev] as usize != index {
-                prev = nexts[prev] as usize;
-                assert!(prev < index);
+        if nodes.len() < self.node_used.len() {
+            nodes.resize(self.node_used.len(), 0);
+        }
+        base as u32
+    }
+
+    fn find_base(&mut self, arcs: &[u8]) -> usize {
+        let front = arcs[0] as usize;
+        let mut curr = self.head;
+        while let Some(index) = curr {
+            curr = self.next_free(index);
+            if index < front {
+                continue;
+            }
+            if self.can_allocate(index - front, &arcs[1..]) {
+                return index - front;
             }
 
-            let next = nexts[index] as usize;
-            if self.head == index {
-                self.head = next;
+            let block = index / BLOCK_SIZE;
+            self.blocks[block].retries += 1;
+            if self.blocks[block].retries >= MAX_BLOCK_RETRIES {
+                while curr.map_or(false, |i| i / BLOCK_SIZE == block) {
+                    curr = self.next_free(curr.unwrap());
+                }
+                self.close_block(block);
             }
-            nexts[prev] = next as u64;
         }
+
+        // Every slot after the end of the array is free
+        let mut base = cmp::max(self.node_used.len(), front + 1) - front;
+        while self.base_used.get(base).unwrap_or(false) {
+            base += 1;
+        }
+        base
     }
 
-    fn can_allocate(&mut self, base: usize, arcs: &[u8]) -> bool {
+    fn can_allocate(&self, base: usize, arcs: &[u8]) -> bool {
         if self.base_used.get(base).unwrap_or(false) {
-            return false; // already used
+            return false;
         }
-        arcs.iter().all(|a| !self.node_used.get(base + *a as usize).unwrap_or(false))
+        arcs.iter().all(|a| {
+            let index = base + *a as usize;
+            index >= self.node_used.len() || self.is_free(index)
+        })
+    }
+
+    fn is_free(&self, index: usize) -> bool {
+        let block = index / BLOCK_SIZE;
+        !self.node_used[index] && block >= self.first_open_block && !self.blocks[block].is_closed
     }
 
-    fn extend_if_needed(&mut self, index: usize, nexts: &mut Vec<u64>) {
-        if index < nexts.len() {
+    fn next_free(&self, index: usize) -> Option<usize> {
+        let next = self.links[index % WINDOW_SIZE].next;
+        if next > index { Some(next) } else { None }
+    }
+
+    // Appends blocks until the array has `len` slots
+    fn extend(&mut self, len: usize) {
+        while self.node_used.len() < len {
+            if self.blocks.len() - self.first_open_block == OPEN_BLOCKS {
+                let block = self.first_open_block;
+                self.close_block(block);
+                self.first_open_block += 1;
+            }
+
+            let start = self.node_used.len();
+            self.blocks.push(Block {
+                retries: 0,
+                is_closed: false,
+            });
+            self.base_used.grow(BLOCK_SIZE, false);
+            self.node_used.grow(BLOCK_SIZE, false);
+            for index in start..start + BLOCK_SIZE {
+                self.link(index);
+            }
+            if start == 0 {
+                // the root's slot, and base 0 which marks a node without children
+                self.unlink(0);
+                self.node_used.set(0, true);
+                self.base_used.set(0, true);
+            }
+        }
+    }
+
+    fn close_block(&mut self, block: usize) {
+        if self.blocks[block].is_closed {
             return;
         }
-        let size = nexts.len();
-        for i in size..index + 1 {
-            nexts.push(i as u64 + 1);
+        for index in block * BLOCK_SIZE..(block + 1) * BLOCK_SIZE {
+            if !self.node_used[index] {
+                self.unlink(index);
+            }
         }
-        self.base_used.grow(index + 1 - size, false);
-        self.node_used.grow(index + 1 - size, false);
+        self.blocks[block].is_closed = true;
+    }
+
+    // Adds `index`, which is larger than any free slot, to the tail of the list
+    fn link(&mut self, index: usize) {
+        let head = match self.head {
+            None => {
+                self.head = Some(index);
+                self.links[index % WINDOW_SIZE] = Link {
+                    prev: index,
+                    next: index,
+                };
+                return;
+            }
+            Some(head) => head,
+        };
+        let tail = self.links[head % WINDOW_SIZE].prev;
+        self.links[index % WINDOW_SIZE] = Link {
+            prev: tail,
+            next: head,
+        };
+        self.links[tail % WINDOW_SIZE].next = index;
+        self.links[head % WINDOW_SIZE].prev = index;
     }
-}
 
-fn get_next(index: usize, nexts: &mut Vec<u64>) -> u32 {
-    nexts.get(index).map(|n| *n as u32).unwrap_or(index as u32 + 1)
+    fn unlink(&mut self, index: usize) {
+        let Link { prev, next } = self.links[index % WINDOW_SIZE];
+        if next == index {
+            self.head = None;
+            return;
+        }
+        self.links[prev % WINDOW_SIZE].next = next;
+        self.links[next % WINDOW_SIZE].prev = prev;
+        if self.head == Some(index) {
+            self.head = Some(next);
+        }
+    }
 }
@@ -3,26 +3,37 @@
 // This software is released under the MIT License,
 // see the LICENSE file at the top-level directory.
 
-use std::rc::Rc;
+use std::cmp;
+use std::sync::Arc;
+use std::thread;
 use std::collections::HashMap;
-use binary_tree::NodeAddr;
 use binary_tree::Trie as BinTreeTrie;
 use binary_tree::Node as BinTreeNode;
 use double_array::Base;
 use double_array::Chck;
 use double_array::Trie;
 use double_array::allocator::Allocator;
+use Char;
+use Error;
 
 pub struct Builder {
     memo: Memo,
     allocator: Allocator,
     nodes: Vec<u64>,
     exts: Vec<u32>,
+    threads: usize,
 }
 
-type Memo = HashMap<NodeAddr, Base>;
+// Keyed by the address of a child list
+type Memo = HashMap<usize, Base>;
+
+// The base of a node and its children which are still to be laid out
+type PendingChildren = Option<(Base, Vec<Arc<BinTreeNode>>)>;
 type U24 = u32;
 
+const MAX_BASE: Base = (1 << 29) - 1;
+const MAX_EXT_COUNT: usize = 1 << 24;
+
 struct Node {
     chck: Chck,
     is_terminal: bool,
@@ -115,35 +126,74 @@ impl Builder {
             allocator: Allocator::new(),
             nodes: Vec::new(),
             exts: Vec::new(),
+            threads: 1,
         }
     }
 
-    pub fn build(mut self, trie: BinTreeTrie) -> Trie {
+    /// Makes a builder which lays out the subgraphs under the root's children on `threads`
+    /// threads, each into its own region of the arrays.
+    ///
+    /// The output is deterministic for a given number of threads, but it is usually a little
+    /// larger than a single-threaded one because nodes are not shared between regions.
+    pub fn with_threads(threads: usize) -> Self {
+        let mut builder = Builder::new();
+        builder.threads = cmp::max(threads, 1);
+        builder
+    }
+
+    pub fn build(mut self, trie: BinTreeTrie) -> Result<Trie<'static>, Error> {
         let bt_root = trie.to_node();
+        if bt_root.child.is_none() {
+            self.nodes.push(0); // the root slot is never allocated if the trie has no children
+        }
         let da_root = Node::new(0, &bt_root);
-        self.build_impl(Rc::new(bt_root), da_root);
-        Trie::new(self.nodes, self.exts)
+        if self.threads > 1 {
+            return self.build_parallel(Arc::new(bt_root), da_root);
+        }
+        try!(self.build_impl(Arc::new(bt_root), da_root));
+        Ok(Trie::new(self.nodes, self.exts))
+    }
+
+    fn build_impl(&mut self, bt_node: Arc<BinTreeNode>, da_node: Node) -> Result<(), Error> {
+        let index = da_node.index as usize;
+        let (n, children) = try!(self.layout_node(bt_node, da_node));
+        assert!(index < self.nodes.len());
+        self.nodes[index] = n;
+        self.build_children(children)
+    }
+
+    fn build_children(&mut self, children: PendingChildren) -> Result<(), Error> {
+        if let Some((base, children)) = children {
+            for bt_child in children.into_iter() {
+                let da_child = Node::new(base, &bt_child);
+                try!(self.build_impl(bt_child, da_child));
+            }
+        }
+        Ok(())
     }
 
-    fn build_impl(&mut self, mut bt_node: Rc<BinTreeNode>, mut da_node: Node) {
+    // Finds the base of the node and returns the node encoded, along with its children to lay
+    // out next (None if there are no children or they are already laid out).
+    fn layout_node(&mut self,
+                   mut bt_node: Arc<BinTreeNode>,
+                   mut da_node: Node)
+                   -> Result<(u64, PendingChildren), Error> {
         let mut children: Vec<_>;
         let mut memo_key;
         let mut do_memoize;
         loop {
             if bt_node.child.is_none() {
-                self.fix_node(da_node, 0);
-                return;
+                return self.encode_node(&da_node, 0).map(|n| (n, None));
             }
 
-            memo_key = bt_node.child.as_ref().unwrap().addr();
+            memo_key = bt_node.child.as_ref().unwrap().addr() as usize;
             if let Some(base) = self.memo.get(&memo_key).cloned() {
-                self.fix_node(da_node, base);
-                return;
+                return self.encode_node(&da_node, base).map(|n| (n, None));
             }
 
-            children = match Rc::try_unwrap(bt_node) {
+            children = match Arc::try_unwrap(bt_node) {
                 Ok(mut bt_node) => {
-                    do_memoize = Rc::get_mut(bt_node.child.as_mut().unwrap()).is_none();
+                    do_memoize = Arc::get_mut(bt_node.child.as_mut().unwrap()).is_none();
                     bt_node.take_children().collect()
                 }
                 Err(bt_node) => {
@@ -166,17 +216,83 @@ impl Builder {
             let chars = children.iter().map(|c| c.ch).collect::<Vec<_>>();
             self.allocator.allocate(&chars, &mut self.nodes)
         };
+        if base > MAX_BASE {
+            return Err(Error::Limit(format!("too many nodes: base={}", base)));
+        }
         if do_memoize {
             self.memo.insert(memo_key, base);
         }
-        self.fix_node(da_node, base);
-        for bt_child in children.into_iter() {
-            let da_child = Node::new(base, &bt_child);
-            self.build_impl(bt_child, da_child);
+        let n = try!(self.encode_node(&da_node, base));
+        Ok((n, Some((base, children))))
+    }
+
+    // The subgraphs under the root's children are shared between the threads as they are,
+    // since the nodes of a binary-tree trie are `Send + Sync`
+    fn build_parallel(mut self,
+                      bt_root: Arc<BinTreeNode>,
+                      da_root: Node)
+                      -> Result<Trie<'static>, Error> {
+        let (n, children) = try!(self.layout_node(bt_root, da_root));
+        self.nodes[0] = n;
+        let (base, units) = match children {
+            None => return Ok(Trie::new(self.nodes, self.exts)),
+            Some(x) => x,
+        };
+
+        let workers = split_units(units, self.threads)
+            .into_iter()
+            .map(|units| thread::spawn(move || Builder::build_region(units)))
+            .collect::<Vec<_>>();
+        for worker in workers {
+            let region = try!(worker.join().expect("a build thread panicked"));
+            try!(self.stitch(base, region));
+        }
+        Ok(Trie::new(self.nodes, self.exts))
+    }
+
+    // Lays out the subgraphs under `units` into fresh arrays.
+    // The nodes of the units themselves are returned separately as they belong to the parent.
+    fn build_region(units: Vec<Arc<BinTreeNode>>) -> Result<Region, Error> {
+        let mut builder = Builder::new();
+        let mut unit_nodes = Vec::new();
+        for unit in units {
+            let ch = unit.ch;
+            let da_node = Node::new(0, &unit);
+            let (n, children) = try!(builder.layout_node(unit, da_node));
+            unit_nodes.push((ch, n));
+            try!(builder.build_children(children));
+        }
+        Ok(Region {
+            nodes: builder.nodes,
+            exts: builder.exts,
+            unit_nodes: unit_nodes,
+        })
+    }
+
+    // Appends `region` to the arrays, relocating its bases and ext indices
+    fn stitch(&mut self, parent_base: Base, region: Region) -> Result<(), Error> {
+        let offset = self.nodes.len() as Base;
+        let ext_offset = self.exts.len() as u32;
+        if offset as usize + region.nodes.len() > MAX_BASE as usize + 1 {
+            return Err(Error::Limit(format!("too many nodes: base={}",
+                                            offset as usize + region.nodes.len())));
+        }
+        if ext_offset as usize + region.exts.len() > MAX_EXT_COUNT {
+            return Err(Error::Limit(format!("too many exts: count={}",
+                                            ext_offset as usize + region.exts.len())));
+        }
+
+        for n in region.nodes {
+            self.nodes.push(relocate(n, offset, ext_offset));
+        }
+        self.exts.extend(region.exts);
+        for (ch, n) in region.unit_nodes {
+            self.nodes[(parent_base + ch as u32) as usize] = relocate(n, offset, ext_offset);
         }
+        Ok(())
     }
 
-    fn fix_node(&mut self, node: Node, base: Base) {
+    fn encode_node(&mut self, node: &Node, base: Base) -> Result<u64, Error> {
         let n = mask(base as u64, 0, 29) + mask(node.info.type_id() as u64, 29, 2) +
                 mask(node.is_terminal as u64, 31, 1) +
                 mask(node.chck as u64, 32, 8);
@@ -191,15 +307,64 @@ impl Builder {
             }
             &NodeInfo::Type2{id_offset} => n + mask(id_offset as u64, 40, 24),
             &NodeInfo::Type3{id_offset} => {
+                if self.exts.len() >= MAX_EXT_COUNT {
+                    return Err(Error::Limit(format!("too many exts: count={}",
+                                                    self.exts.len() + 1)));
+                }
                 self.exts.push(id_offset);
                 n + mask((self.exts.len() - 1) as u64, 40, 24)
             }
         };
-        assert!((node.index as usize) < self.nodes.len());
-        self.nodes[node.index as usize] = n;
+        Ok(n)
     }
 }
 
 fn mask(x: u64, offset: usize, size: usize) -> u64 {
     (x & ((1 << size) - 1)) << offset
 }
+
+fn unmask(n: u64, offset: usize, size: usize) -> u64 {
+    (n >> offset) & ((1 << size) - 1)
+}
+
+// The nodes laid out by a build thread, in the coordinates of its own arrays
+struct Region {
+    nodes: Vec<u64>,
+    exts: Vec<u32>,
+    unit_nodes: Vec<(Char, u64)>,
+}
+
+// Moves the encoded node `n` from a region into the arrays at `offset` and `ext_offset`.
+// Only the region's unused root slot, whose check byte is 0, is cleared. The other unused slots
+// are 0 already.
+fn relocate(mut n: u64, offset: Base, ext_offset: u32) -> u64 {
+    if unmask(n, 32, 8) == 0 {
+        return 0;
+    }
+    if unmask(n, 0, 29) != 0 {
+        n += offset as u64;
+    }
+    if unmask(n, 29, 2) == 3 {
+        n += mask(ext_offset as u64, 40, 24);
+    }
+    n
+}
+
+// Splits `units` into at most `threads` groups that have about the same number of words
+fn split_units(mut units: Vec<Arc<BinTreeNode>>, threads: usize) -> Vec<Vec<Arc<BinTreeNode>>> {
+    units.sort_by_key(|u| cmp::Reverse(word_count(u)));
+    let mut groups = (0..cmp::min(threads, units.len()))
+                         .map(|_| (0, Vec::new()))
+                         .collect::<Vec<_>>();
+    for unit in units {
+        let lightest = groups.iter_mut().min_by_key(|g| g.0).unwrap();
+        lightest.0 += word_count(&unit);
+        lightest.1.push(unit);
+    }
+    groups.into_iter().map(|g| g.1).collect()
+}
+
+// The number of the words which pass through `node`
+fn word_count(node: &BinTreeNode) -> usize {
+    node.len() - node.id_offset() as usize
+}
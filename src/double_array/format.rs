@@ -0,0 +1,124 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+// Layout of an index file (all integers are little-endian):
+//
+// | offset | size | field                                  |
+// |--------|------|----------------------------------------|
+// |      0 |    4 | magic number (`MAGIC`)                 |
+// |      4 |    4 | format version (`FORMAT_VERSION`)      |
+// |      8 |    4 | node layout version (`LAYOUT_VERSION`) |
+// |     12 |    4 | reserved (always zero)                 |
+// |     16 |    8 | number of keys                         |
+// |     24 |    8 | number of nodes                        |
+// |     32 |    8 | number of exts                         |
+// |     40 |    8 | checksum of the nodes and exts         |
+// |     48 |  8*N | nodes                                  |
+// |  48+8N |  4*M | exts                                   |
+//
+// Legacy (headerless) files start with the byte sizes of the node and ext arrays
+// as native-endian `u32`s. The size of the node array is a multiple of eight,
+// so a legacy file never starts with `MAGIC`.
+
+use std::io::Result as IoResult;
+use std::io::Write;
+use Error;
+
+pub const MAGIC: &[u8; 4] = b"DAWG";
+pub const FORMAT_VERSION: u32 = 1;
+pub const LAYOUT_VERSION: u32 = 1;
+pub const HEADER_SIZE: usize = 48;
+
+pub const MAX_NODE_COUNT: u64 = (1 << 29) + 0x100;
+pub const MAX_EXT_COUNT: u64 = 1 << 24;
+
+pub struct Header {
+    pub key_count: u64,
+    pub node_count: u64,
+    pub ext_count: u64,
+    pub checksum: u64,
+}
+
+impl Header {
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+            return Err(invalid_data("missing index header"));
+        }
+        let version = read_u32(&bytes[4..8]);
+        if version != FORMAT_VERSION {
+            return Err(invalid_data(&format!("unsupported format version: {}", version)));
+        }
+        let layout = read_u32(&bytes[8..12]);
+        if layout != LAYOUT_VERSION {
+            return Err(invalid_data(&format!("unsupported node layout version: {}", layout)));
+        }
+        let header = Header {
+            key_count: read_u64(&bytes[16..24]),
+            node_count: read_u64(&bytes[24..32]),
+            ext_count: read_u64(&bytes[32..40]),
+            checksum: read_u64(&bytes[40..48]),
+        };
+        if header.node_count > MAX_NODE_COUNT || header.ext_count > MAX_EXT_COUNT {
+            return Err(invalid_data("too many nodes or exts"));
+        }
+        Ok(header)
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let mut buf = [0; HEADER_SIZE];
+        buf[0..4].copy_from_slice(MAGIC);
+        buf[4..8].copy_from_slice(&FORMAT_VERSION.to_le_bytes());
+        buf[8..12].copy_from_slice(&LAYOUT_VERSION.to_le_bytes());
+        buf[16..24].copy_from_slice(&self.key_count.to_le_bytes());
+        buf[24..32].copy_from_slice(&self.node_count.to_le_bytes());
+        buf[32..40].copy_from_slice(&self.ext_count.to_le_bytes());
+        buf[40..48].copy_from_slice(&self.checksum.to_le_bytes());
+        w.write_all(&buf)
+    }
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[0..4]);
+    u32::from_le_bytes(buf)
+}
+
+fn read_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[0..8]);
+    u64::from_le_bytes(buf)
+}
+
+// FNV-1a applied to each node and ext value rather than to each byte
+pub struct Checksum(u64);
+
+impl Checksum {
+    pub fn new() -> Self {
+        Checksum(0xcbf29ce484222325)
+    }
+
+    pub fn update(&mut self, n: u64) {
+        self.0 = (self.0 ^ n).wrapping_mul(0x100000001b3);
+    }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+pub fn checksum(nodes: &[u64], exts: &[u32]) -> u64 {
+    let mut c = Checksum::new();
+    for n in nodes.iter() {
+        c.update(*n);
+    }
+    for e in exts.iter() {
+        c.update(*e as u64);
+    }
+    c.value()
+}
+
+pub fn invalid_data(reason: &str) -> Error {
+    Error::Format(reason.to_owned())
+}
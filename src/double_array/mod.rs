@@ -6,9 +6,10 @@
 mod builder;
 mod allocator;
 mod trie;
+mod format;
 
 pub type Base = u32;
 pub type Chck = u8;
 
 pub type Builder = builder::Builder;
-pub type Trie = trie::Trie;
+pub type Trie<'a> = trie::Trie<'a>;
@@ -3,6 +3,12 @@
 // This software is released under the MIT License,
 // see the LICENSE file at the top-level directory.
 
+use std::borrow::Cow;
+use std::cmp;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem;
+use std::slice;
 use std::path::Path;
 use std::fs::File;
 use std::io::Result as IoResult;
@@ -10,31 +16,114 @@ use std::io::Write;
 use std::io::BufWriter;
 use std::io::Read;
 use std::io::BufReader;
-use byteorder::ByteOrder;
-use byteorder::NativeEndian;
 use WordId;
-use Word;
+use IntoWord;
 use Char;
+use Error;
+use binary_tree::Trie as BinaryTree;
+use common;
+use common::Automaton;
+use common::AutomatonIter;
 use common::CommonPrefixIter;
+use common::CommonPrefixStrIter;
+use common::FuzzyIter;
+use common::FuzzyStrIter;
+use common::Levenshtein;
+use common::PredictiveIter;
+use common::PredictiveStrIter;
+use common::Pattern;
+use common::Regex;
+use common::RegexIter;
+use common::Stats;
+use common::WildcardIter;
 use common::NodeTraverse;
+use double_array::format;
+use double_array::format::Header;
+use double_array::format::Checksum;
+use double_array::format::invalid_data;
 
-pub struct Trie {
-    nodes: Vec<u64>,
-    exts: Vec<u32>,
+// A trie is immutable and `Send + Sync`, so one instance, whether it owns its arrays or
+// borrows them from `from_bytes`, can be queried from many threads at once through `&Trie`.
+pub struct Trie<'a> {
+    nodes: Cow<'a, [u64]>,
+    exts: Cow<'a, [u32]>,
 }
 
-impl Trie {
+impl<'a> Trie<'a> {
     pub fn new(nodes: Vec<u64>, exts: Vec<u32>) -> Self {
         Trie {
-            nodes: nodes,
-            exts: exts,
+            nodes: Cow::Owned(nodes),
+            exts: Cow::Owned(exts),
         }
     }
 
+    /// Makes a trie which borrows its arrays from `bytes` (e.g. a memory-mapped index file)
+    /// without copying them.
+    ///
+    /// `bytes` must hold the contents written by `save` and must be aligned to an 8 byte
+    /// boundary. Unlike `load`, this does not verify the checksum, which requires reading all
+    /// the data, so broken data is only found by `verify`.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < 4 || &bytes[0..4] != format::MAGIC {
+            return Trie::from_legacy_bytes(bytes);
+        }
+        if cfg!(target_endian = "big") {
+            return Err(invalid_data("can't borrow little-endian index data on a big-endian \
+                                     machine"));
+        }
+
+        let header = try!(Header::parse(bytes));
+        let trie = try!(Trie::borrow_arrays(&bytes[format::HEADER_SIZE..],
+                                            header.node_count as usize,
+                                            header.ext_count as usize));
+        if trie.len() as u64 != header.key_count {
+            return Err(invalid_data("key count mismatch"));
+        }
+        Ok(trie)
+    }
+
+    fn from_legacy_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        if bytes.len() < 8 {
+            return Err(invalid_data("too short index data"));
+        }
+        let node_size = read_native_u32(&bytes[0..4]) as usize;
+        let ext_size = read_native_u32(&bytes[4..8]) as usize;
+        if node_size & 7 != 0 || ext_size & 3 != 0 {
+            return Err(invalid_data("broken index data"));
+        }
+        Trie::borrow_arrays(&bytes[8..], node_size / 8, ext_size / 4)
+    }
+
+    fn borrow_arrays(bytes: &'a [u8], node_count: usize, ext_count: usize) -> Result<Self, Error> {
+        // The sizes may overflow on 32-bit targets
+        let node_size = node_count.checked_mul(8);
+        let ext_size = ext_count.checked_mul(4);
+        let total_size = node_size.and_then(|n| ext_size.and_then(|e| n.checked_add(e)));
+        if total_size.map_or(true, |total| bytes.len() < total) {
+            return Err(invalid_data("broken index data"));
+        }
+        let (node_size, ext_size) = (node_size.unwrap(), ext_size.unwrap());
+        if bytes.as_ptr() as usize & (mem::align_of::<u64>() - 1) != 0 {
+            return Err(invalid_data("index data is not aligned to an 8 byte boundary"));
+        }
+
+        let nodes = &bytes[0..node_size];
+        let exts = &bytes[node_size..node_size + ext_size];
+        Ok(Trie {
+            nodes: Cow::Borrowed(unsafe {
+                slice::from_raw_parts(nodes.as_ptr() as *const u64, node_count)
+            }),
+            exts: Cow::Borrowed(unsafe {
+                slice::from_raw_parts(exts.as_ptr() as *const u32, ext_count)
+            }),
+        })
+    }
+
     pub fn len(&self) -> usize {
         let mut count = 0;
         let mut node = NodeTraverser::new(self);
-        loop {
+
+        for _ in 0..node.max_depth() + 1 {
             count += node.is_terminal() as usize + node.id_offset() as usize;
             for i in 0x00.. {
                 if i == 0xFF {
@@ -47,79 +136,511 @@ impl Trie {
                 }
             }
         }
+        count
     }
 
-    pub fn contains(&self, word: Word) -> bool {
+    // The ratio of the slots holding a node to all the slots
+    pub fn fill_rate(&self) -> f64 {
+        if self.nodes.is_empty() {
+            return 0.0;
+        }
+        self.used_slot_count() as f64 / self.nodes.len() as f64
+    }
+
+    pub fn stats(&self) -> Stats {
+        let mut stats = Stats {
+            key_count: self.len(),
+            slot_count: self.nodes.len(),
+            used_slot_count: self.used_slot_count(),
+            ext_count: self.exts.len(),
+            ..Stats::default()
+        };
+        let root = match self.nodes.first() {
+            None => return stats,
+            Some(root) => *root,
+        };
+
+        // The number of the parents of each base, and of the children at it
+        let mut bases: HashMap<u32, (usize, usize)> = HashMap::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let n = self.nodes[index];
+            stats.node_types[mask(n, 29, 2) as usize] += 1;
+            stats.encoded_child_count += encoded_child_count(n);
+            stats.node_count += encoded_child_count(n);
+            if base(n) == 0 {
+                continue;
+            }
+            let entry = bases.entry(base(n)).or_insert((0, 0));
+            entry.0 += 1;
+            if entry.0 == 1 {
+                let children = self.children_at(base(n));
+                entry.1 = children.len();
+                stats.node_count += children.len();
+                stack.extend(children);
+            }
+        }
+        stats.shared_node_count = bases.values().filter(|b| b.0 > 1).map(|b| b.1).sum();
+
+        let (words, bytes) = self.words_below(base(root), &mut HashMap::new());
+        stats.key_bytes = encoded_child_count(root) * words + bytes;
+        stats
+    }
+
+    /// Checks that the arrays make a well-formed trie, e.g. before deploying an index file.
+    ///
+    /// Every reachable slot must hold the label of its arc as the check byte, and no other slot
+    /// may be used. Encoded children must be non-zero and lead to a word, ext indices must be in
+    /// range, and the id offsets must number the words from 0 to `len() - 1` without gaps or
+    /// overlaps. The first violation found is returned as `Error::Format`.
+    pub fn verify(&self) -> Result<(), Error> {
+        let root = match self.nodes.first() {
+            None => return Ok(()),
+            Some(root) => *root,
+        };
+        if mask(root, 32, 8) != 0 {
+            return Err(broken(0, "the root has a non-zero check byte"));
+        }
+        try!(self.verify_node(0));
+
+        // The number of the words below each base, or None until all of its children are done
+        let mut totals: HashMap<u32, Option<usize>> = HashMap::new();
+        let mut reachable = vec![false; self.nodes.len()];
+        reachable[0] = true;
+        let mut stack = vec![(base(root), None)];
+        while let Some((b, children)) = stack.pop() {
+            if b == 0 {
+                continue;
+            }
+            let children = match children {
+                Some(children) => children,
+                None => {
+                    match totals.get(&b) {
+                        Some(&Some(_)) => continue,
+                        Some(&None) => {
+                            return Err(invalid_data(&format!("base {} is in a cycle", b)))
+                        }
+                        None => {}
+                    }
+                    let children = self.children_at(b);
+                    if children.is_empty() {
+                        return Err(invalid_data(&format!("no children at base {}", b)));
+                    }
+                    totals.insert(b, None);
+                    stack.push((b, Some(children.clone())));
+                    for i in children {
+                        reachable[i] = true;
+                        try!(self.verify_node(i));
+                        stack.push((base(self.nodes[i]), None));
+                    }
+                    continue;
+                }
+            };
+
+            let mut total = 0;
+            for i in children {
+                let n = self.nodes[i];
+                if id_offset(n, &self.exts) as usize != total {
+                    return Err(broken(i,
+                                      &format!("id offset {} where {} is expected",
+                                               id_offset(n, &self.exts),
+                                               total)));
+                }
+                total += is_terminal(n) as usize + totals.get(&base(n)).map_or(0, |t| t.unwrap());
+            }
+            totals.insert(b, Some(total));
+        }
+
+        if id_offset(root, &self.exts) != 0 {
+            return Err(broken(0, "the root has a non-zero id offset"));
+        }
+        let count = is_terminal(root) as usize + totals.get(&base(root)).map_or(0, |t| t.unwrap());
+        if count != self.len() {
+            return Err(invalid_data(&format!("{} words are numbered but the length is {}",
+                                             count,
+                                             self.len())));
+        }
+        match (1..self.nodes.len()).find(|i| !reachable[*i] && mask(self.nodes[*i], 32, 8) != 0) {
+            None => Ok(()),
+            Some(i) => Err(broken(i, "the slot is used but unreachable")),
+        }
+    }
+
+    /// Checks that the trie holds exactly `words`, which must be sorted and distinct.
+    pub fn verify_words<I>(&self, words: I) -> Result<(), Error>
+        where I: IntoIterator,
+              I::Item: IntoWord
+    {
+        let mut count = 0;
+        for word in words {
+            let word = word.into_word().collect::<Vec<_>>();
+            let id = self.get_id(&word);
+            if id != Some(count as WordId) {
+                return Err(invalid_data(&format!("the word {:?} has id {:?} where {} is \
+                                                  expected",
+                                                 String::from_utf8_lossy(&word),
+                                                 id,
+                                                 count)));
+            }
+            count += 1;
+        }
+        if count != self.len() {
+            return Err(invalid_data(&format!("the index has {} words but {} are given",
+                                             self.len(),
+                                             count)));
+        }
+        Ok(())
+    }
+
+    fn verify_node(&self, index: usize) -> Result<(), Error> {
+        let n = self.nodes[index];
+        let encoded = encoded_child_count(n);
+        let max_encoded = match mask(n, 29, 2) {
+            0 => 2,
+            1 => 1,
+            _ => 0,
+        };
+        if (encoded..max_encoded).any(|i| mask(n, 40 + 8 * i, 8) != 0) {
+            return Err(broken(index, "an encoded child follows a zero byte"));
+        }
+        if mask(n, 29, 2) == 3 && mask(n, 40, 24) as usize >= self.exts.len() {
+            return Err(broken(index,
+                              &format!("ext index {} is out of range", mask(n, 40, 24))));
+        }
+        if base(n) == 0 && (encoded != 0 || !is_terminal(n)) && index != 0 {
+            return Err(broken(index, "no word ends below the node"));
+        }
+        Ok(())
+    }
+
+    // Only the root's slot has a zero check byte among the used ones
+    fn used_slot_count(&self) -> usize {
+        if self.nodes.is_empty() {
+            return 0;
+        }
+        1 + self.nodes[1..].iter().filter(|n| mask(**n, 32, 8) != 0).count()
+    }
+
+    // Returns the indices of the slots of the children at `base`
+    fn children_at(&self, base: u32) -> Vec<usize> {
+        (1..0x100)
+            .map(|ch| (ch, base as usize + ch))
+            .filter(|&(ch, i)| self.nodes.get(i).map_or(false, |n| mask(*n, 32, 8) == ch as u64))
+            .map(|(_, i)| i)
+            .collect()
+    }
+
+    // Returns the number of the words below the base `b` and the total length of their parts
+    // below it. A base in a cycle, which `verify` reports, counts as having no words.
+    fn words_below(&self,
+                   b: u32,
+                   memo: &mut HashMap<u32, Option<(usize, usize)>>)
+                   -> (usize, usize) {
+        if b == 0 {
+            return (0, 0);
+        }
+        if let Some(total) = memo.get(&b) {
+            return total.unwrap_or((0, 0));
+        }
+        memo.insert(b, None);
+        let mut total = (0, 0);
+        for n in self.children_at(b).into_iter().map(|i| self.nodes[i]) {
+            let (words, bytes) = self.words_below(base(n), memo);
+            let through = is_terminal(n) as usize + words;
+            total.0 += through;
+            total.1 += through + encoded_child_count(n) * words + bytes;
+        }
+        memo.insert(b, Some(total));
+        total
+    }
+
+    pub fn contains<W: IntoWord>(&self, word: W) -> bool {
         self.get_id(word).is_some()
     }
 
-    pub fn get_id(&self, word: Word) -> Option<WordId> {
-        let word_len = word.len();
-        self.search_common_prefix(word).find(|m| word_len == m.1).map(|m| m.0)
+    pub fn get_id<W: IntoWord>(&self, word: W) -> Option<WordId> {
+        common::get_id(word, NodeTraverser::new(self))
     }
 
-    pub fn search_common_prefix<'a, 'b>(&'a self,
-                                        word: Word<'b>)
-                                        -> CommonPrefixIter<'b, NodeTraverser<'a>> {
+    pub fn get_word(&self, id: WordId) -> Option<Vec<Char>> {
+        common::get_word(NodeTraverser::new(self), id)
+    }
+
+    pub fn search_common_prefix<'b, W: IntoWord>(&'b self,
+                                                 word: W)
+                                                 -> CommonPrefixIter<W::Iter, NodeTraverser<'b>> {
         CommonPrefixIter::new(word, NodeTraverser::new(self))
     }
 
-    pub fn load<P: AsRef<Path>>(index_file_path: P) -> IoResult<Self> {
+    pub fn longest_prefix<W: IntoWord>(&self, word: W) -> Option<(WordId, usize)> {
+        self.longest_prefix_with_depth(word).0
+    }
+
+    // The second element is the length of the longest prefix of `word` in the trie,
+    // whether or not a word ends there.
+    pub fn longest_prefix_with_depth<W: IntoWord>(&self,
+                                                  word: W)
+                                                  -> (Option<(WordId, usize)>, usize) {
+        common::longest_prefix(word, NodeTraverser::new(self))
+    }
+
+    pub fn search_predictive<'b, W: IntoWord>(&'b self,
+                                              prefix: W)
+                                              -> PredictiveIter<NodeTraverser<'b>> {
+        PredictiveIter::new(prefix, NodeTraverser::new(self))
+    }
+
+    pub fn iter<'b>(&'b self) -> PredictiveIter<NodeTraverser<'b>> {
+        self.search_predictive("".bytes())
+    }
+
+    pub fn search_fuzzy<'b, W: IntoWord>(&'b self,
+                                         word: W,
+                                         max_distance: usize)
+                                         -> FuzzyIter<NodeTraverser<'b>> {
+        FuzzyIter::new(Levenshtein::new(word, max_distance, false),
+                       NodeTraverser::new(self))
+    }
+
+    pub fn search_fuzzy_with_transposition<'b, W: IntoWord>(&'b self,
+                                                            word: W,
+                                                            max_distance: usize)
+                                                            -> FuzzyIter<NodeTraverser<'b>> {
+        FuzzyIter::new(Levenshtein::new(word, max_distance, true),
+                       NodeTraverser::new(self))
+    }
+
+    // The `_str` variants work on characters rather than bytes: prefixes never end inside
+    // a character, edits are counted in Unicode scalar values and words that are not valid
+    // UTF-8 are skipped.
+    pub fn search_common_prefix_str<'b, 'c>(&'b self,
+                                            text: &'c str)
+                                            -> CommonPrefixStrIter<'c, NodeTraverser<'b>> {
+        CommonPrefixStrIter::new(text, NodeTraverser::new(self))
+    }
+
+    pub fn search_predictive_str<'b>(&'b self,
+                                     prefix: &str)
+                                     -> PredictiveStrIter<NodeTraverser<'b>> {
+        PredictiveStrIter::new(prefix, NodeTraverser::new(self))
+    }
+
+    pub fn search_fuzzy_str<'b>(&'b self,
+                                word: &str,
+                                max_distance: usize)
+                                -> FuzzyStrIter<NodeTraverser<'b>> {
+        FuzzyStrIter::new(FuzzyIter::new(Levenshtein::new_utf8(word, max_distance, false),
+                                         NodeTraverser::new(self)))
+    }
+
+    pub fn search_fuzzy_str_with_transposition<'b>(&'b self,
+                                                   word: &str,
+                                                   max_distance: usize)
+                                                   -> FuzzyStrIter<NodeTraverser<'b>> {
+        FuzzyStrIter::new(FuzzyIter::new(Levenshtein::new_utf8(word, max_distance, true),
+                                         NodeTraverser::new(self)))
+    }
+
+    pub fn search<'b, A: Automaton>(&'b self,
+                                    automaton: A)
+                                    -> AutomatonIter<A, NodeTraverser<'b>> {
+        AutomatonIter::new(automaton, NodeTraverser::new(self))
+    }
+
+    // Supports `?` (any byte), `*` (any sequence of bytes), byte classes such as `[a-c]`
+    // or `[!a-c]`, and `\` to escape the next byte.
+    pub fn search_wildcard<'b>(&'b self,
+                               pattern: &str)
+                               -> Result<WildcardIter<NodeTraverser<'b>>, Error> {
+        let pattern = try!(Pattern::parse(pattern));
+        Ok(WildcardIter::new(pattern, NodeTraverser::new(self)))
+    }
+
+    // See `common::Regex` for the supported syntax. Patterns match whole words.
+    pub fn search_regex<'b>(&'b self,
+                            pattern: &str)
+                            -> Result<RegexIter<NodeTraverser<'b>>, Error> {
+        let regex = try!(Regex::new(pattern));
+        Ok(RegexIter::new(regex, NodeTraverser::new(self)))
+    }
+
+    pub fn union(&self, other: &Trie) -> BinaryTree {
+        common::union(NodeTraverser::new(self), NodeTraverser::new(other))
+    }
+
+    pub fn intersection(&self, other: &Trie) -> BinaryTree {
+        common::intersection(NodeTraverser::new(self), NodeTraverser::new(other))
+    }
+
+    pub fn difference(&self, other: &Trie) -> BinaryTree {
+        common::difference(NodeTraverser::new(self), NodeTraverser::new(other))
+    }
+
+    pub fn is_subset(&self, other: &Trie) -> bool {
+        common::is_subset(NodeTraverser::new(self), NodeTraverser::new(other))
+    }
+
+    pub fn load<P: AsRef<Path>>(index_file_path: P) -> Result<Self, Error> {
         let mut r = BufReader::new(try!(File::open(index_file_path)));
-        let node_count = try!(read_u32(&mut r)) / 8;
-        let ext_count = try!(read_u32(&mut r)) / 4;
+        Trie::read_from(&mut r)
+    }
 
-        let mut nodes = Vec::with_capacity(node_count as usize);
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let mut buf = [0; format::HEADER_SIZE];
+        try!(r.read_exact(&mut buf[0..4]));
+        if &buf[0..4] != format::MAGIC {
+            let node_count = read_native_u32(&buf[0..4]) / 8;
+            return Trie::read_legacy(r, node_count);
+        }
+        try!(r.read_exact(&mut buf[4..]));
+        let header = try!(Header::parse(&buf));
+
+        let mut checksum = Checksum::new();
+        let mut nodes = Vec::with_capacity(initial_capacity(header.node_count));
+        for _ in 0..header.node_count {
+            let n = try!(read_le_u64(r));
+            checksum.update(n);
+            nodes.push(n);
+        }
+
+        let mut exts = Vec::with_capacity(initial_capacity(header.ext_count));
+        for _ in 0..header.ext_count {
+            let e = try!(read_le_u32(r));
+            checksum.update(e as u64);
+            exts.push(e);
+        }
+
+        if checksum.value() != header.checksum {
+            return Err(invalid_data("checksum mismatch"));
+        }
+        let trie = Trie::new(nodes, exts);
+        if trie.len() as u64 != header.key_count {
+            return Err(invalid_data("key count mismatch"));
+        }
+        Ok(trie)
+    }
+
+    fn read_legacy<R: Read>(r: &mut R, node_count: u32) -> Result<Self, Error> {
+        let ext_count = try!(read_ne_u32(r)) / 4;
+
+        let mut nodes = Vec::with_capacity(initial_capacity(node_count as u64));
         for _ in 0..node_count {
-            nodes.push(try!(read_u64(&mut r)));
+            nodes.push(try!(read_ne_u64(r)));
         }
 
-        let mut exts = Vec::with_capacity(ext_count as usize);
+        let mut exts = Vec::with_capacity(initial_capacity(ext_count as u64));
         for _ in 0..ext_count {
-            exts.push(try!(read_u32(&mut r)));
+            exts.push(try!(read_ne_u32(r)));
         }
 
         Ok(Trie::new(nodes, exts))
     }
 
-    pub fn save<P: AsRef<Path>>(&self, index_file_path: P) -> IoResult<()> {
+    pub fn save<P: AsRef<Path>>(&self, index_file_path: P) -> Result<(), Error> {
         let mut w = BufWriter::new(try!(File::create(index_file_path)));
-        try!(write_u32(&mut w, self.nodes.len() as u32 * 8));
-        try!(write_u32(&mut w, self.exts.len() as u32 * 4));
+        try!(self.write_to(&mut w));
+        try!(w.flush());
+        Ok(())
+    }
+
+    // Writes the used slots in the Graphviz DOT language. Each slot shows its index, label,
+    // base and node type, and the encoded children follow their parent as dashed boxes.
+    pub fn to_dot<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        try!(writeln!(w, "digraph dawg {{"));
+        try!(writeln!(w, "  node [shape=box];"));
+        let mut visited = HashSet::new();
+        let mut stack = if self.nodes.is_empty() { vec![] } else { vec![0] };
+        while let Some(index) = stack.pop() {
+            let n = self.nodes[index];
+            let label = if index == 0 {
+                "root".to_string()
+            } else {
+                common::dot_label(mask(n, 32, 8) as Char)
+            };
+            try!(writeln!(w,
+                          "  s{} [label=\"[{}] {}\\nbase={} type={}\"{}];",
+                          index,
+                          index,
+                          label,
+                          base(n),
+                          mask(n, 29, 2),
+                          if is_terminal(n) { " peripheries=2" } else { "" }));
+
+            let mut parent = format!("s{}", index);
+            for i in 0..encoded_child_count(n) {
+                let name = format!("s{}_{}", index, i + 1);
+                try!(writeln!(w,
+                              "  {} [label=\"{}\" style=dashed];",
+                              name,
+                              common::dot_label(mask(n, 40 + 8 * i, 8) as Char)));
+                try!(writeln!(w, "  {} -> {};", parent, name));
+                parent = name;
+            }
+            if base(n) == 0 {
+                continue;
+            }
+            for child in self.children_at(base(n)) {
+                try!(writeln!(w, "  {} -> s{};", parent, child));
+                if visited.insert(child) {
+                    stack.push(child);
+                }
+            }
+        }
+        try!(writeln!(w, "}}"));
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        let header = Header {
+            key_count: self.len() as u64,
+            node_count: self.nodes.len() as u64,
+            ext_count: self.exts.len() as u64,
+            checksum: format::checksum(&self.nodes, &self.exts),
+        };
+        try!(header.write_to(w));
         for n in self.nodes.iter() {
-            try!(write_u64(&mut w, *n));
+            try!(w.write_all(&n.to_le_bytes()));
         }
         for e in self.exts.iter() {
-            try!(write_u32(&mut w, *e));
+            try!(w.write_all(&e.to_le_bytes()));
         }
         Ok(())
     }
 }
 
-fn read_u32<R: Read>(r: &mut R) -> IoResult<u32> {
+// Counts in a broken header must not cause a huge allocation before the data turns out to be short
+fn initial_capacity(count: u64) -> usize {
+    cmp::min(count, 0x100000) as usize
+}
+
+fn read_native_u32(bytes: &[u8]) -> u32 {
     let mut buf = [0; 4];
-    let size = try!(r.read(&mut buf));
-    assert_eq!(size, buf.len());
-    Ok(NativeEndian::read_u32(&buf))
+    buf.copy_from_slice(&bytes[0..4]);
+    u32::from_ne_bytes(buf)
 }
 
-fn read_u64<R: Read>(r: &mut R) -> IoResult<u64> {
+fn read_le_u32<R: Read>(r: &mut R) -> IoResult<u32> {
+    let mut buf = [0; 4];
+    try!(r.read_exact(&mut buf));
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_le_u64<R: Read>(r: &mut R) -> IoResult<u64> {
     let mut buf = [0; 8];
-    let size = try!(r.read(&mut buf));
-    assert_eq!(size, buf.len());
-    Ok(NativeEndian::read_u64(&buf))
+    try!(r.read_exact(&mut buf));
+    Ok(u64::from_le_bytes(buf))
 }
 
-fn write_u32<W: Write>(w: &mut W, n: u32) -> IoResult<()> {
+fn read_ne_u32<R: Read>(r: &mut R) -> IoResult<u32> {
     let mut buf = [0; 4];
-    NativeEndian::write_u32(&mut buf, n);
-    w.write_all(&mut buf)
+    try!(r.read_exact(&mut buf));
+    Ok(u32::from_ne_bytes(buf))
 }
 
-fn write_u64<W: Write>(w: &mut W, n: u64) -> IoResult<()> {
+fn read_ne_u64<R: Read>(r: &mut R) -> IoResult<u64> {
     let mut buf = [0; 8];
-    NativeEndian::write_u64(&mut buf, n);
-    w.write_all(&mut buf)
+    try!(r.read_exact(&mut buf));
+    Ok(u64::from_ne_bytes(buf))
 }
 
 fn base(n: u64) -> u32 {
@@ -134,47 +655,70 @@ fn mask(n: u64, offset: usize, size: usize) -> u64 {
     (n >> offset) & ((1 << size) - 1)
 }
 
+fn id_offset(n: u64, exts: &[u32]) -> u32 {
+    match mask(n, 29, 2) {
+        0 => mask(n, 56, 8) as u32,
+        1 => mask(n, 48, 16) as u32,
+        2 => mask(n, 40, 24) as u32,
+        3 => exts.get(mask(n, 40, 24) as usize).cloned().unwrap_or(0),
+        _ => unreachable!(),
+    }
+}
+
+fn broken(index: usize, reason: &str) -> Error {
+    invalid_data(&format!("slot {}: {}", index, reason))
+}
+
+fn encoded_child_count(n: u64) -> usize {
+    let max = match mask(n, 29, 2) {
+        0 => 2,
+        1 => 1,
+        _ => 0,
+    };
+    (0..max).take_while(|i| mask(n, 40 + 8 * i, 8) != 0).count()
+}
+
+impl<'a, 'b> PartialEq<Trie<'b>> for Trie<'a> {
+    fn eq(&self, other: &Trie<'b>) -> bool {
+        self.len() == other.len() &&
+        common::is_equal(NodeTraverser::new(self), NodeTraverser::new(other))
+    }
+}
+
+#[derive(Clone)]
 pub struct NodeTraverser<'a> {
     node: u64,
-    nodes: &'a Vec<u64>,
-    exts: &'a Vec<u32>,
+    encoded: usize,
+    nodes: &'a [u64],
+    exts: &'a [u32],
 }
 
 impl<'a> NodeTraverse for NodeTraverser<'a> {
     fn is_terminal(&self) -> bool {
-        is_terminal(self.node)
+        self.encoded == 0 && is_terminal(self.node)
     }
 
     fn id_offset(&self) -> u32 {
-        let n = self.node;
-        let node_type = mask(n, 29, 2);
-        match node_type {
-            0 => mask(n, 56, 8) as u32,
-            1 => mask(n, 48, 16) as u32,
-            2 => mask(n, 40, 24) as u32,
-            3 => self.exts[mask(n, 40, 24) as usize],
-            _ => unreachable!(),
+        if self.encoded != 0 {
+            return 0;
         }
+        id_offset(self.node, self.exts)
     }
 
-    fn jump(&mut self, word: &mut Word) -> Option<()> {
-        self.check_encoded_children(word)
-            .and_then(|_| word.next().and_then(|ch| self.jump_char(ch)))
-    }
-}
-
-impl<'a> NodeTraverser<'a> {
-    pub fn new(trie: &'a Trie) -> Self {
-        NodeTraverser {
-            node: trie.nodes[0],
-            nodes: &trie.nodes,
-            exts: &trie.exts,
+    fn jump_char(&mut self, ch: Char) -> Option<()> {
+        let encoded_ch = self.encoded_child(self.encoded);
+        if encoded_ch != 0 {
+            return if ch == encoded_ch {
+                self.encoded += 1;
+                Some(())
+            } else {
+                None
+            };
         }
-    }
 
-    fn jump_char(&mut self, ch: Char) -> Option<()> {
+        // A base of 0 is never allocated, so such a node has no children but the encoded ones
         let base = base(self.node) as usize;
-        if self.nodes.len() <= base + ch as usize {
+        if base == 0 || self.nodes.len() <= base + ch as usize {
             return None;
         }
 
@@ -182,28 +726,64 @@ impl<'a> NodeTraverser<'a> {
         let chck = mask(next, 32, 8) as Char;
         if ch == chck {
             self.node = next;
+            self.encoded = 0;
             Some(())
         } else {
             None
         }
     }
 
-    fn check_encoded_children(&mut self, word: &mut Word) -> Option<()> {
+    fn ch(&self) -> Char {
+        if self.encoded == 0 {
+            mask(self.node, 32, 8) as Char
+        } else {
+            self.encoded_child(self.encoded - 1)
+        }
+    }
+
+    fn children(&self) -> Vec<Self> {
+        if self.encoded_child(self.encoded) != 0 {
+            let mut child = self.clone();
+            child.encoded += 1;
+            return vec![child];
+        }
+        (1..0x100)
+            .filter_map(|ch| {
+                let mut child = self.clone();
+                child.jump_char(ch as Char).map(|_| child)
+            })
+            .collect()
+    }
+
+    // Each node has at most two encoded children, so a path in a well-formed trie never
+    // exceeds this length
+    fn max_depth(&self) -> usize {
+        self.nodes.len() * 3
+    }
+}
+
+impl<'a> NodeTraverser<'a> {
+    pub fn new(trie: &'a Trie) -> Self {
+        NodeTraverser {
+            node: trie.nodes.first().cloned().unwrap_or(0),
+            encoded: 0,
+            nodes: &trie.nodes,
+            exts: &trie.exts,
+        }
+    }
+
+    // Returns the `i`-th child inlined into the current node, or `0` if there is none.
+    fn encoded_child(&self, i: usize) -> Char {
         let node_type = mask(self.node, 29, 2);
         let max = match node_type {
             0 => 2,
             1 => 1,
             _ => 0,
         };
-        for i in 0..max {
-            let c = mask(self.node, 40 + 8 * i, 8) as Char;
-            if c == 0 {
-                return Some(());
-            }
-            if !word.next().map_or(false, |ch| ch == c) {
-                return None;
-            }
+        if i < max {
+            mask(self.node, 40 + 8 * i, 8) as Char
+        } else {
+            0
         }
-        Some(())
     }
 }
@@ -0,0 +1,64 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fmt::Error as FmtError;
+use std::io::Error as IoError;
+use binary_tree::InsertError;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(IoError),
+    Format(String),
+    Limit(String),
+    Insert(InsertError),
+    Pattern(String),
+}
+
+impl StdError for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(_) => "I/O error",
+            Error::Format(_) => "malformed index data",
+            Error::Limit(_) => "limit exceeded",
+            Error::Insert(_) => "insertion error",
+            Error::Pattern(_) => "invalid pattern",
+        }
+    }
+
+    fn cause(&self) -> Option<&dyn StdError> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            Error::Insert(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match *self {
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Format(ref reason) => write!(f, "malformed index data: {}", reason),
+            Error::Limit(ref reason) => write!(f, "limit exceeded: {}", reason),
+            Error::Insert(ref e) => write!(f, "{}", e),
+            Error::Pattern(ref reason) => write!(f, "invalid pattern: {}", reason),
+        }
+    }
+}
+
+impl From<IoError> for Error {
+    fn from(e: IoError) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<InsertError> for Error {
+    fn from(e: InsertError) -> Self {
+        Error::Insert(e)
+    }
+}
@@ -4,16 +4,145 @@
 // see the LICENSE file at the top-level directory.
 
 extern crate bit_vec;
-extern crate byteorder;
 
+use std::borrow::Borrow;
+use std::iter::Cloned;
+use std::iter::Map;
+use std::slice;
 use std::str::Bytes;
+use std::vec;
 
 pub mod binary_tree;
 pub mod double_array;
 pub mod common;
+pub mod map;
+mod error;
 
 pub type Char = u8;
 pub type WordId = u32;
 pub type Word<'a> = Bytes<'a>;
 
+/// Anything that yields the characters of a word: `&str`, `String`, `&[u8]`, `&[u8; N]`,
+/// `Vec<u8>` and the byte iterators `str::Bytes`, `slice::Iter<u8>` and `vec::IntoIter<u8>`.
+/// Any other iterator of bytes can be passed by wrapping it in `WordIter`.
+/// Every method that takes a word, e.g. `insert`, `get_id`, `contains` and
+/// `search_common_prefix`, accepts any of them.
+pub trait IntoWord {
+    type Iter: Iterator<Item = Char>;
+    fn into_word(self) -> Self::Iter;
+}
+
+/// Passes any iterator of bytes, e.g. bytes read from a stream, where a word is expected.
+///
+/// A blanket implementation of `IntoWord` for byte iterators would conflict with the ones
+/// for `&str`, `&[u8]` and the like, so the other iterators are wrapped in this instead.
+///
+/// ```
+/// use std::io::Read;
+/// use dawg::WordIter;
+/// use dawg::binary_tree::Builder;
+///
+/// let stream: &[u8] = b"\xFFkey";
+/// let mut builder = Builder::new();
+/// builder.insert(WordIter(stream.bytes().map(|b| b.unwrap()))).unwrap();
+/// let trie = builder.finish();
+/// assert!(trie.contains(WordIter(stream.bytes().map(|b| b.unwrap()))));
+/// ```
+pub struct WordIter<I>(pub I);
+
+impl<I: IntoIterator> IntoWord for WordIter<I>
+    where I::Item: Borrow<Char>
+{
+    type Iter = Map<I::IntoIter, fn(I::Item) -> Char>;
+
+    fn into_word(self) -> Self::Iter {
+        fn to_char<B: Borrow<Char>>(ch: B) -> Char {
+            *ch.borrow()
+        }
+        self.0.into_iter().map(to_char::<I::Item>)
+    }
+}
+
+impl<'a> IntoWord for &'a str {
+    type Iter = Bytes<'a>;
+
+    fn into_word(self) -> Self::Iter {
+        self.bytes()
+    }
+}
+
+impl<'a> IntoWord for &'a String {
+    type Iter = Bytes<'a>;
+
+    fn into_word(self) -> Self::Iter {
+        self.bytes()
+    }
+}
+
+impl IntoWord for String {
+    type Iter = vec::IntoIter<Char>;
+
+    fn into_word(self) -> Self::Iter {
+        self.into_bytes().into_iter()
+    }
+}
+
+impl<'a> IntoWord for &'a [Char] {
+    type Iter = Cloned<slice::Iter<'a, Char>>;
+
+    fn into_word(self) -> Self::Iter {
+        self.iter().cloned()
+    }
+}
+
+impl<'a, const N: usize> IntoWord for &'a [Char; N] {
+    type Iter = Cloned<slice::Iter<'a, Char>>;
+
+    fn into_word(self) -> Self::Iter {
+        self.iter().cloned()
+    }
+}
+
+impl<'a> IntoWord for &'a Vec<Char> {
+    type Iter = Cloned<slice::Iter<'a, Char>>;
+
+    fn into_word(self) -> Self::Iter {
+        self.iter().cloned()
+    }
+}
+
+impl IntoWord for Vec<Char> {
+    type Iter = vec::IntoIter<Char>;
+
+    fn into_word(self) -> Self::Iter {
+        self.into_iter()
+    }
+}
+
+impl<'a> IntoWord for Bytes<'a> {
+    type Iter = Self;
+
+    fn into_word(self) -> Self::Iter {
+        self
+    }
+}
+
+impl<'a> IntoWord for slice::Iter<'a, Char> {
+    type Iter = Cloned<Self>;
+
+    fn into_word(self) -> Self::Iter {
+        self.cloned()
+    }
+}
+
+impl IntoWord for vec::IntoIter<Char> {
+    type Iter = Self;
+
+    fn into_word(self) -> Self::Iter {
+        self
+    }
+}
+
+pub type Error = error::Error;
+
 pub const EOS: Char = 0 as Char;
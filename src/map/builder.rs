@@ -0,0 +1,51 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use Char;
+use IntoWord;
+use Error;
+use binary_tree::Builder as BinTreeBuilder;
+use binary_tree::InsertError;
+use double_array::Builder as DoubleArrayBuilder;
+use map::DawgMap;
+use map::Value;
+
+pub struct Builder<V> {
+    builder: BinTreeBuilder,
+    values: Vec<V>,
+    last_word: Option<Vec<Char>>,
+}
+
+impl<V: Value> Builder<V> {
+    pub fn new() -> Self {
+        Builder {
+            builder: BinTreeBuilder::new(),
+            values: Vec::new(),
+            last_word: None,
+        }
+    }
+
+    // Words must be inserted in sorted order, so that the n-th value belongs to the n-th word.
+    // If the same word is inserted again, its value is overwritten.
+    pub fn insert<W: IntoWord>(&mut self, word: W, value: V) -> Result<(), InsertError> {
+        let bytes = word.into_word().collect::<Vec<_>>();
+        if self.last_word.as_ref() == Some(&bytes) {
+            *self.values.last_mut().unwrap() = value;
+            return Ok(());
+        }
+        if self.last_word.as_ref().map_or(false, |last| bytes < *last) {
+            return Err(InsertError::Unsorted);
+        }
+        try!(self.builder.insert(&bytes));
+        self.values.push(value);
+        self.last_word = Some(bytes);
+        Ok(())
+    }
+
+    pub fn finish(self) -> Result<DawgMap<V>, Error> {
+        let trie = try!(DoubleArrayBuilder::new().build(self.builder.finish()));
+        DawgMap::new(trie, self.values)
+    }
+}
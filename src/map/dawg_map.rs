@@ -0,0 +1,123 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+use std::cmp;
+use std::path::Path;
+use std::fs::File;
+use std::io::Read;
+use std::io::BufReader;
+use std::io::Write;
+use std::io::BufWriter;
+use WordId;
+use IntoWord;
+use Error;
+use double_array::Trie;
+use map::Value;
+
+// A map file consists of an index (see `double_array::Trie::save`) followed by the values:
+//
+// | size | field                                |
+// |------|--------------------------------------|
+// |    4 | size of a value in bytes             |
+// |    8 | number of values (= number of words) |
+// |  S*N | values (little-endian)               |
+pub struct DawgMap<V> {
+    trie: Trie<'static>,
+    values: Vec<V>,
+}
+
+impl<V: Value> DawgMap<V> {
+    pub fn new(trie: Trie<'static>, values: Vec<V>) -> Result<Self, Error> {
+        if trie.len() != values.len() {
+            return Err(Error::Format(format!("the number of values ({}) differs from the \
+                                              number of words ({})",
+                                             values.len(),
+                                             trie.len())));
+        }
+        Ok(DawgMap {
+            trie: trie,
+            values: values,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn trie(&self) -> &Trie<'static> {
+        &self.trie
+    }
+
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    pub fn contains<W: IntoWord>(&self, word: W) -> bool {
+        self.trie.contains(word)
+    }
+
+    pub fn get<W: IntoWord>(&self, word: W) -> Option<V> {
+        self.trie.get_id(word).and_then(|id| self.get_by_id(id))
+    }
+
+    pub fn get_by_id(&self, id: WordId) -> Option<V> {
+        self.values.get(id as usize).cloned()
+    }
+
+    pub fn load<P: AsRef<Path>>(map_file_path: P) -> Result<Self, Error> {
+        let mut r = BufReader::new(try!(File::open(map_file_path)));
+        DawgMap::read_from(&mut r)
+    }
+
+    pub fn read_from<R: Read>(r: &mut R) -> Result<Self, Error> {
+        let trie = try!(Trie::read_from(r));
+
+        let mut buf = [0; 4];
+        try!(r.read_exact(&mut buf));
+        let value_size = u32::from_le_bytes(buf) as usize;
+        let mut buf = [0; 8];
+        try!(r.read_exact(&mut buf));
+        let value_count = u64::from_le_bytes(buf);
+        if value_size != V::size() {
+            return Err(Error::Format(format!("value size mismatch: expected={}, actual={}",
+                                             V::size(),
+                                             value_size)));
+        }
+        if value_count != trie.len() as u64 {
+            return Err(Error::Format(format!("value count mismatch: expected={}, actual={}",
+                                             trie.len(),
+                                             value_count)));
+        }
+
+        let mut values = Vec::with_capacity(cmp::min(value_count, 0x100000) as usize);
+        let mut buf = vec![0; value_size];
+        for _ in 0..value_count {
+            try!(r.read_exact(&mut buf));
+            values.push(V::read(&buf));
+        }
+        DawgMap::new(trie, values)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, map_file_path: P) -> Result<(), Error> {
+        let mut w = BufWriter::new(try!(File::create(map_file_path)));
+        try!(self.write_to(&mut w));
+        try!(w.flush());
+        Ok(())
+    }
+
+    pub fn write_to<W: Write>(&self, w: &mut W) -> Result<(), Error> {
+        try!(self.trie.write_to(w));
+
+        try!(w.write_all(&(V::size() as u32).to_le_bytes()));
+        try!(w.write_all(&(self.values.len() as u64).to_le_bytes()));
+
+        let mut buf = vec![0; V::size()];
+        for v in self.values.iter() {
+            v.write(&mut buf);
+            try!(w.write_all(&buf));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+mod builder;
+mod dawg_map;
+mod value;
+
+pub use self::value::Value;
+
+pub type Builder<V> = builder::Builder<V>;
+pub type DawgMap<V> = dawg_map::DawgMap<V>;
@@ -0,0 +1,42 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+// A fixed-size value which is stored in little-endian
+pub trait Value: Copy {
+    fn size() -> usize;
+    fn read(buf: &[u8]) -> Self;
+    fn write(&self, buf: &mut [u8]);
+}
+
+macro_rules! impl_value {
+    ($t:ty, $size:expr) => {
+        impl Value for $t {
+            fn size() -> usize {
+                $size
+            }
+
+            fn read(buf: &[u8]) -> Self {
+                let mut bytes = [0; $size];
+                bytes.copy_from_slice(&buf[0..$size]);
+                <$t>::from_le_bytes(bytes)
+            }
+
+            fn write(&self, buf: &mut [u8]) {
+                buf[0..$size].copy_from_slice(&self.to_le_bytes());
+            }
+        }
+    }
+}
+
+impl_value!(u8, 1);
+impl_value!(u16, 2);
+impl_value!(u32, 4);
+impl_value!(u64, 8);
+impl_value!(i8, 1);
+impl_value!(i16, 2);
+impl_value!(i32, 4);
+impl_value!(i64, 8);
+impl_value!(f32, 4);
+impl_value!(f64, 8);
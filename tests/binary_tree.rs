@@ -5,7 +5,15 @@
 
 extern crate dawg;
 
+use std::sync::Arc;
+use std::thread;
+use dawg::Error;
+use dawg::WordIter;
 use dawg::binary_tree::Builder;
+use dawg::binary_tree::Trie;
+use dawg::common::Automaton;
+use dawg::common::Pattern;
+use dawg::common::Regex;
 
 #[test]
 fn build() {
@@ -16,6 +24,40 @@ fn build() {
     assert_eq!(words().len(), b.finish().len());
 }
 
+#[test]
+fn str_words() {
+    let words = vec!["b".to_string(), "bbb".to_string()];
+    let mut b = Builder::new();
+    b.insert("abc").unwrap();
+    for w in words.iter() {
+        b.insert(w).unwrap();
+    }
+    let trie = b.finish();
+
+    assert_eq!(Some(0), trie.get_id("abc"));
+    assert_eq!(Some(1), trie.get_id(&words[0]));
+    assert_eq!(Some(2), trie.get_id("bbb".to_string()));
+    assert!(trie.contains("b"));
+    assert!(!trie.contains("ab"));
+    assert_eq!(vec![(1, 1), (2, 3)],
+               trie.search_common_prefix("bbbb").collect::<Vec<_>>());
+}
+
+#[test]
+fn insert_error() {
+    let mut b = Builder::new();
+    assert!(b.insert("b".bytes()).is_ok());
+    assert!(b.insert("a".bytes()).is_err());
+    assert!(b.insert("b\0".bytes()).is_err());
+    assert!(b.insert("c".bytes()).is_ok());
+
+    // Rejected words do not affect the words inserted so far
+    let trie = b.finish();
+    assert_eq!(2, trie.len());
+    assert!(trie.contains("b".bytes()));
+    assert!(trie.contains("c".bytes()));
+}
+
 #[test]
 fn search_common_prefix() {
     let trie = words()
@@ -35,6 +77,445 @@ fn search_common_prefix() {
                trie.search_common_prefix("cddrr".bytes()).collect::<Vec<_>>());
 }
 
+#[test]
+fn longest_prefix() {
+    let trie = words()
+                   .iter()
+                   .fold(Builder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+
+    assert_eq!(None, trie.longest_prefix("hoge".bytes()));
+    assert_eq!(Some((5, 4)), trie.longest_prefix("cddrx".bytes()));
+    assert_eq!(Some((4, 2)), trie.longest_prefix("cdd".bytes()));
+    assert_eq!(Some((1, 1)), trie.longest_prefix("bb".bytes()));
+
+    assert_eq!((None, 0), trie.longest_prefix_with_depth("hoge".bytes()));
+    assert_eq!((None, 2), trie.longest_prefix_with_depth("cat".bytes()));
+    assert_eq!((Some((4, 2)), 3), trie.longest_prefix_with_depth("cdd".bytes()));
+    assert_eq!((Some((6, 3)), 3), trie.longest_prefix_with_depth("cdr".bytes()));
+}
+
+#[test]
+fn get_word() {
+    let trie = words()
+                   .iter()
+                   .fold(Builder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+
+    for (id, w) in words().iter().enumerate() {
+        assert_eq!(Some(id as u32), trie.get_id(w.bytes()));
+        assert_eq!(Some(w.as_bytes().to_vec()), trie.get_word(id as u32));
+    }
+    assert_eq!(None, trie.get_word(words().len() as u32));
+}
+
+#[test]
+fn search_predictive() {
+    let trie = words()
+                   .iter()
+                   .fold(Builder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+
+    assert_eq!(0, trie.search_predictive("hoge".bytes()).count());
+
+    assert_eq!(vec![(0, b"abc".to_vec())],
+               trie.search_predictive("ab".bytes()).collect::<Vec<_>>());
+
+    assert_eq!(vec![(4, b"cd".to_vec()), (5, b"cddr".to_vec()), (6, b"cdr".to_vec())],
+               trie.search_predictive("cd".bytes()).collect::<Vec<_>>());
+
+    assert_eq!(words().len(), trie.search_predictive("".bytes()).count());
+}
+
+#[test]
+fn iter() {
+    let trie = words()
+                   .iter()
+                   .fold(Builder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+
+    let expected = words()
+                       .iter()
+                       .enumerate()
+                       .map(|(id, w)| (id as u32, w.as_bytes().to_vec()))
+                       .collect::<Vec<_>>();
+    assert_eq!(expected, trie.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn build_unsorted() {
+    let mut b = Builder::unsorted();
+    for w in ["cdr", "b", "cddr", "abc", "cd", "car", "bbb", "b"].iter() {
+        assert!(b.insert(w.bytes()).is_ok());
+    }
+    assert!(b.insert("a\0".bytes()).is_err());
+    let trie = b.finish();
+
+    assert_eq!(words().len(), trie.len());
+    for (id, w) in words().iter().enumerate() {
+        assert_eq!(Some(id as u32), trie.get_id(w.bytes()));
+    }
+}
+
+#[test]
+fn search_fuzzy() {
+    let trie = words()
+                   .iter()
+                   .fold(Builder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+
+    assert_eq!(0, trie.search_fuzzy("hoge".bytes(), 1).count());
+
+    assert_eq!(vec![(3, b"car".to_vec(), 1),
+                    (4, b"cd".to_vec(), 1),
+                    (5, b"cddr".to_vec(), 1),
+                    (6, b"cdr".to_vec(), 0)],
+               trie.search_fuzzy("cdr".bytes(), 1).collect::<Vec<_>>());
+
+    assert_eq!(0, trie.search_fuzzy("acr".bytes(), 1).count());
+    assert_eq!(vec![(3, b"car".to_vec(), 1)],
+               trie.search_fuzzy_with_transposition("acr".bytes(), 1).collect::<Vec<_>>());
+}
+
+// Accepts the words of at most the given length
+struct MaxLength(usize);
+
+impl Automaton for MaxLength {
+    type State = usize;
+    fn start(&self) -> usize {
+        0
+    }
+    fn step(&self, len: &usize, _: u8) -> usize {
+        len + 1
+    }
+    fn is_match(&self, _: &usize) -> bool {
+        true
+    }
+    fn can_match(&self, len: &usize) -> bool {
+        *len <= self.0
+    }
+}
+
+#[test]
+fn search() {
+    let trie = words()
+                   .iter()
+                   .fold(Builder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+
+    assert_eq!(0, trie.search(MaxLength(0)).count());
+    assert_eq!(vec![(1, b"b".to_vec()), (4, b"cd".to_vec())],
+               trie.search(MaxLength(2)).collect::<Vec<_>>());
+    assert_eq!(7, trie.search(MaxLength(4)).count());
+
+    let regex = Regex::new("c.*r").unwrap();
+    assert_eq!(vec![(3, b"car".to_vec()), (5, b"cddr".to_vec()), (6, b"cdr".to_vec())],
+               trie.search(&regex).collect::<Vec<_>>());
+    let pattern = Pattern::parse("?b*").unwrap();
+    assert_eq!(vec![(0, b"abc".to_vec()), (2, b"bbb".to_vec())],
+               trie.search(&pattern).collect::<Vec<_>>());
+}
+
+#[test]
+fn regex_limits() {
+    assert!(Regex::new("(a{10}){10}").is_ok());
+    match Regex::new("a{1001}") {
+        Err(Error::Limit(_)) => {}
+        _ => panic!("the repetition count must be limited"),
+    }
+
+    // Nested repetitions multiply the size of the automaton
+    for pattern in ["((a{1000}){1000}){3}", "(((a{1000}){1000}){1000}){1000}", "(a{2,1000}){1000}"]
+                       .iter() {
+        match Regex::new(pattern) {
+            Err(Error::Limit(_)) => {}
+            _ => panic!("{} must exceed a limit", pattern),
+        }
+    }
+
+    // Long chains of states must compile quickly
+    assert!(Regex::new(&".{1000}".repeat(9)).is_ok());
+
+    // Deep nesting must not overflow the stack
+    let nested = |depth| format!("{}a{}", "(".repeat(depth), ")".repeat(depth));
+    assert!(Regex::new(&nested(100)).is_ok());
+    let long_chains = ["a".to_string() + &"?".repeat(200000),
+                       "a".to_string() + &"*".repeat(200000),
+                       "(a?)".to_string() + &"*?".repeat(200000)];
+    for pattern in [nested(100000)].iter().chain(long_chains.iter()) {
+        match Regex::new(pattern) {
+            Err(Error::Limit(_)) => {}
+            _ => panic!("the nesting of {:.10}... must be limited", pattern),
+        }
+    }
+}
+
+#[test]
+fn non_utf8_words() {
+    let words: [&[u8]; 4] = [b"\x01\xff", b"\x80", b"\x80\x80", b"\xfe"];
+    let mut builder = Builder::new();
+    for w in words.iter() {
+        builder.insert(*w).unwrap();
+    }
+    let trie = builder.finish();
+
+    assert_eq!(4, trie.len());
+    assert_eq!(Some(0), trie.get_id([1, 255].iter()));
+    assert_eq!(Some(2), trie.get_id(vec![128, 128]));
+    assert_eq!(Some(3), trie.get_id(b"\xfe"));
+    assert_eq!(Some(3), trie.get_id(WordIter(Some(254))));
+    assert_eq!(None, trie.get_id(b"\x01"));
+    assert!(trie.contains(WordIter((0..2).map(|_| 128))));
+    assert!(!trie.contains(b""));
+    assert_eq!(None, trie.get_id("\u{80}"));
+    assert_eq!(vec![(1, 1), (2, 2)],
+               trie.search_common_prefix(b"\x80\x80\x80").collect::<Vec<_>>());
+    assert_eq!(Some(b"\x80\x80".to_vec()), trie.get_word(2));
+}
+
+#[test]
+fn insert() {
+    let mut trie = words()
+                       .iter()
+                       .fold(Builder::new(), |mut b, w| {
+                           b.insert(w.bytes()).ok().unwrap();
+                           b
+                       })
+                       .finish();
+
+    for w in ["cdrr", "ca", "abc", "aaa", "cd"].iter() {
+        trie.insert(w.bytes()).unwrap();
+    }
+    assert!(trie.insert(b"a\0").is_err());
+    assert_eq!(10, trie.len());
+
+    let mut all = words().iter().chain(["cdrr", "ca", "aaa"].iter()).cloned().collect::<Vec<_>>();
+    all.sort();
+    let expected = all.iter()
+                      .enumerate()
+                      .map(|(i, w)| (i as u32, w.as_bytes().to_vec()))
+                      .collect::<Vec<_>>();
+    assert_eq!(expected, trie.iter().collect::<Vec<_>>());
+    for (id, w) in expected {
+        assert_eq!(Some(id), trie.get_id(&w));
+    }
+
+    trie.insert("".bytes()).unwrap();
+    assert_eq!(Some(0), trie.get_id(b""));
+    assert_eq!(Some(1), trie.get_id(b"aaa"));
+}
+
+#[test]
+fn remove() {
+    let mut trie = words()
+                       .iter()
+                       .fold(Builder::new(), |mut b, w| {
+                           b.insert(w.bytes()).ok().unwrap();
+                           b
+                       })
+                       .finish();
+
+    assert_eq!(None, trie.remove("c".bytes()));
+    assert_eq!(None, trie.remove("cddrr".bytes()));
+    assert_eq!(None, trie.remove("".bytes()));
+    assert_eq!(7, trie.len());
+
+    assert_eq!(Some((4, 5..7)), trie.remove("cd".bytes()));
+    assert_eq!(Some((1, 2..6)), trie.remove("b".bytes()));
+    assert_eq!(Some((3, 4..5)), trie.remove("cddr".bytes()));
+    assert_eq!(None, trie.remove("cd".bytes()));
+    assert_eq!(vec![(0, b"abc".to_vec()),
+                    (1, b"bbb".to_vec()),
+                    (2, b"car".to_vec()),
+                    (3, b"cdr".to_vec())],
+               trie.iter().collect::<Vec<_>>());
+
+    trie.insert("cd".bytes()).unwrap();
+    assert_eq!(Some(3), trie.get_id("cd".bytes()));
+    for w in ["abc", "bbb", "car", "cd", "cdr"].iter() {
+        assert!(trie.remove(w.bytes()).is_some());
+    }
+    assert_eq!(0, trie.len());
+    assert_eq!(0, trie.iter().count());
+}
+
+#[test]
+fn update_while_iterating() {
+    let root = words()
+                       .iter()
+                       .fold(Builder::new(), |mut b, w| {
+                           b.insert(w.bytes()).ok().unwrap();
+                           b
+                       })
+                       .finish()
+                       .to_node();
+    let old = Arc::downgrade(root.child.as_ref().unwrap());
+    let mut trie = Trie::new(root);
+
+    {
+        let iter = trie.iter();
+        trie.insert("aaa".bytes()).unwrap();
+        assert!(trie.remove("cd".bytes()).is_some());
+        assert_eq!(words().to_vec(),
+                   iter.map(|(_, w)| String::from_utf8(w).unwrap()).collect::<Vec<_>>());
+    }
+    // The replaced nodes are freed once the iterator is gone
+    assert!(old.upgrade().is_none());
+    assert_eq!(vec!["aaa", "abc", "b", "bbb", "car", "cddr", "cdr"],
+               trie.iter().map(|(_, w)| String::from_utf8(w).unwrap()).collect::<Vec<_>>());
+}
+
+#[test]
+fn stats() {
+    let trie = ["abc", "b", "bbc", "c"].iter().fold(Builder::new(), |mut b, w| {
+        b.insert(w.bytes()).unwrap();
+        b
+    }).finish();
+    let stats = trie.stats();
+    assert_eq!(4, stats.key_count);
+    assert_eq!(8, stats.key_bytes);
+    assert_eq!(5, stats.node_count);
+    assert_eq!(1, stats.shared_node_count); // "b" -> "c" under "a" and "b"
+    assert_eq!(0, stats.slot_count);
+}
+
+#[test]
+fn to_dot() {
+    let trie = ["a", "ab", "b\""].iter().fold(Builder::new(), |mut b, w| {
+        b.insert(w.bytes()).unwrap();
+        b
+    }).finish();
+    let mut dot = Vec::new();
+    trie.to_dot(&mut dot).unwrap();
+    assert_eq!("digraph dawg {
+  node [shape=circle];
+  n0 [label=\"root\"];
+  n0 -> n1 [style=solid];
+  n1 [label=\"b\"];
+  n1 -> n2 [style=solid];
+  n1 -> n3 [style=dashed];
+  n3 [label=\"a\" shape=doublecircle];
+  n3 -> n4 [style=solid];
+  n4 [label=\"b\" shape=doublecircle];
+  n2 [label=\"\\\"\" shape=doublecircle];
+}
+",
+               String::from_utf8(dot).unwrap());
+}
+
+#[test]
+fn set_operations() {
+    let build = |words: &[&str]| {
+        let mut builder = Builder::new();
+        for w in words.iter() {
+            builder.insert(w.bytes()).unwrap();
+        }
+        builder.finish()
+    };
+    let a = build(&words());
+    let b = build(&["b", "bb", "cd", "cdr", "x"]);
+    let words_of = |trie: dawg::binary_tree::Trie| {
+        trie.iter().map(|(_, w)| String::from_utf8(w).unwrap()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec!["abc", "b", "bb", "bbb", "car", "cd", "cddr", "cdr", "x"],
+               words_of(a.union(&b)));
+    assert_eq!(vec!["b", "cd", "cdr"], words_of(a.intersection(&b)));
+    assert_eq!(vec!["abc", "bbb", "car", "cddr"], words_of(a.difference(&b)));
+    assert_eq!(vec!["bb", "x"], words_of(b.difference(&a)));
+    assert_eq!(0, a.difference(&a).len());
+
+    assert!(a.is_subset(&a));
+    assert!(!a.is_subset(&b));
+    assert!(build(&["b", "cd"]).is_subset(&b));
+    assert!(!build(&["c"]).is_subset(&b));
+
+    assert!(a == build(&words()));
+    assert!(a != b);
+    assert!(build(&["b", "cd"]) != build(&["b", "cdr"]));
+    assert!(a.union(&b) == b.union(&a));
+}
+
+#[test]
+fn build_minimal() {
+    let mut words = Vec::new();
+    for prefix in ["", "pre", "re", "un"].iter() {
+        for stem in ["do", "make", "view"].iter() {
+            for suffix in ["", "ing", "s", "ed"].iter() {
+                words.push(format!("{}{}{}", prefix, stem, suffix));
+            }
+        }
+    }
+    words.sort();
+
+    // Finished suffixes are merged as the sorted input moves past them, so the graph is as
+    // small as the one which merges every inserted word at once
+    let sorted = words.iter().fold(Builder::new(), |mut b, w| {
+        b.insert(w).unwrap();
+        b
+    });
+    let unsorted = words.iter().rev().fold(Builder::unsorted(), |mut b, w| {
+        b.insert(w).unwrap();
+        b
+    });
+    let (sorted, unsorted) = (sorted.finish().stats(), unsorted.finish().stats());
+    assert_eq!(words.len(), sorted.key_count);
+    assert_eq!(unsorted.node_count, sorted.node_count);
+    assert_eq!(23, sorted.node_count);
+}
+
+#[test]
+fn share_between_threads() {
+    let mut trie = words()
+                       .iter()
+                       .fold(Builder::new(), |mut b, w| {
+                           b.insert(w.bytes()).ok().unwrap();
+                           b
+                       })
+                       .finish();
+
+    // An iterator made before an update still yields the old words
+    let old = trie.iter();
+    trie.insert("aaa".bytes()).unwrap();
+    let old = thread::spawn(move || old.count()).join().unwrap();
+    assert_eq!(7, old);
+
+    let trie = Arc::new(trie);
+    let workers = (0..4)
+                      .map(|_| {
+                          let trie = trie.clone();
+                          thread::spawn(move || {
+                              assert_eq!(vec![(5, 2), (6, 4)],
+                                         trie.search_common_prefix("cddrr".bytes())
+                                             .collect::<Vec<_>>());
+                              assert_eq!(Some(b"abc".to_vec()), trie.get_word(1));
+                              trie.iter().count()
+                          })
+                      })
+                      .collect::<Vec<_>>();
+    for worker in workers {
+        assert_eq!(8, worker.join().unwrap());
+    }
+}
+
 fn words() -> [&'static str; 7] {
     ["abc", "b", "bbb", "car", "cd", "cddr", "cdr"]
 }
@@ -5,8 +5,21 @@
 
 extern crate dawg;
 
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::io::Write;
+use std::slice;
+use std::sync::Arc;
+use std::thread;
 use dawg::binary_tree::Builder as BinTreeBuilder;
 use dawg::double_array::Builder as DoubleArrayBuilder;
+use dawg::double_array::Trie;
+use dawg::common::Automaton;
+use dawg::common::Pattern;
+use dawg::common::Regex;
+use dawg::Error;
+use dawg::WordIter;
 
 #[test]
 fn build() {
@@ -14,7 +27,7 @@ fn build() {
     for w in words().iter() {
         assert!(b.insert(w.bytes()).is_ok());
     }
-    let trie = DoubleArrayBuilder::new().build(b.finish());
+    let trie = DoubleArrayBuilder::new().build(b.finish()).unwrap();
     assert_eq!(words().len(), trie.len());
 }
 
@@ -27,7 +40,7 @@ fn search_common_prefix() {
                        b
                    })
                    .finish();
-    let trie = DoubleArrayBuilder::new().build(trie);
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
 
     assert_eq!(0, trie.search_common_prefix("hoge".bytes()).count());
 
@@ -36,8 +49,759 @@ fn search_common_prefix() {
 
     assert_eq!(vec![(4, 2), (5, 4)],
                trie.search_common_prefix("cddrr".bytes()).collect::<Vec<_>>());
+    assert_eq!(vec![(4, 2), (5, 4)],
+               trie.search_common_prefix("cddrr").collect::<Vec<_>>());
+    assert_eq!(Some(0), trie.get_id("abc"));
+    assert!(trie.contains("cdr".to_string()));
+}
+
+#[test]
+fn longest_prefix() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    assert_eq!(None, trie.longest_prefix("hoge".bytes()));
+    assert_eq!(Some((5, 4)), trie.longest_prefix("cddrx".bytes()));
+    assert_eq!(Some((4, 2)), trie.longest_prefix("cdd".bytes()));
+    assert_eq!(Some((1, 1)), trie.longest_prefix("bb".bytes()));
+
+    assert_eq!((None, 0), trie.longest_prefix_with_depth("hoge".bytes()));
+    assert_eq!((None, 2), trie.longest_prefix_with_depth("cat".bytes()));
+    assert_eq!((Some((4, 2)), 3), trie.longest_prefix_with_depth("cdd".bytes()));
+    assert_eq!((Some((6, 3)), 3), trie.longest_prefix_with_depth("cdr".bytes()));
+}
+
+#[test]
+fn get_word() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    for (id, w) in words().iter().enumerate() {
+        assert_eq!(Some(id as u32), trie.get_id(w.bytes()));
+        assert_eq!(Some(w.as_bytes().to_vec()), trie.get_word(id as u32));
+    }
+    assert_eq!(None, trie.get_word(words().len() as u32));
+}
+
+#[test]
+fn search_predictive() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    assert_eq!(0, trie.search_predictive("hoge".bytes()).count());
+
+    assert_eq!(vec![(0, b"abc".to_vec())],
+               trie.search_predictive("ab".bytes()).collect::<Vec<_>>());
+
+    assert_eq!(vec![(4, b"cd".to_vec()), (5, b"cddr".to_vec()), (6, b"cdr".to_vec())],
+               trie.search_predictive("cd".bytes()).collect::<Vec<_>>());
+
+    assert_eq!(words().len(), trie.search_predictive("".bytes()).count());
+}
+
+#[test]
+fn iter() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    let expected = words()
+                       .iter()
+                       .enumerate()
+                       .map(|(id, w)| (id as u32, w.as_bytes().to_vec()))
+                       .collect::<Vec<_>>();
+    assert_eq!(expected, trie.iter().collect::<Vec<_>>());
+}
+
+#[test]
+fn from_bytes() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    let path = env::temp_dir().join("dawg_test_from_bytes.idx");
+    trie.save(&path).unwrap();
+    let mut data = Vec::new();
+    File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+
+    // Copies the index data into an 8 byte aligned buffer
+    let mut buf = vec![0u64; data.len() / 8 + 1];
+    let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, data.len()) };
+    bytes.copy_from_slice(&data);
+
+    let loaded = Trie::from_bytes(bytes).unwrap();
+    assert_eq!(trie.len(), loaded.len());
+    assert_eq!(trie.iter().collect::<Vec<_>>(),
+               loaded.iter().collect::<Vec<_>>());
+
+    assert!(Trie::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn save_and_load() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    let path = env::temp_dir().join("dawg_test_save_and_load.idx");
+    trie.save(&path).unwrap();
+    let mut data = Vec::new();
+    File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+    assert_eq!(b"DAWG", &data[0..4]);
+    assert_eq!(words().len() as u64, read_le_u64(&data[16..24]));
+
+    let loaded = Trie::load(&path).unwrap();
+    assert_eq!(trie.iter().collect::<Vec<_>>(),
+               loaded.iter().collect::<Vec<_>>());
+
+    // Headerless files written by older versions
+    let node_count = read_le_u64(&data[24..32]) as usize;
+    let ext_count = read_le_u64(&data[32..40]) as usize;
+    let mut legacy = vec![0; 8];
+    legacy[0..4].copy_from_slice(&(node_count as u32 * 8).to_ne_bytes());
+    legacy[4..8].copy_from_slice(&(ext_count as u32 * 4).to_ne_bytes());
+    for i in 0..node_count {
+        legacy.extend_from_slice(&read_le_u64(&data[48 + i * 8..]).to_ne_bytes());
+    }
+    for i in 0..ext_count {
+        let e = read_le_u32(&data[48 + node_count * 8 + i * 4..]);
+        legacy.extend_from_slice(&e.to_ne_bytes());
+    }
+    let legacy_path = env::temp_dir().join("dawg_test_save_and_load_legacy.idx");
+    File::create(&legacy_path).unwrap().write_all(&legacy).unwrap();
+    let loaded = Trie::load(&legacy_path).unwrap();
+    assert_eq!(trie.iter().collect::<Vec<_>>(),
+               loaded.iter().collect::<Vec<_>>());
+
+    // A legacy file has no checksum, so a broken one may have a cycle.
+    // Here the arc 'a' from the root leads back to the root's base.
+    let mut cyclic = vec![0; 8];
+    cyclic[0..4].copy_from_slice(&(200u32 * 8).to_ne_bytes());
+    for i in 0..200 {
+        let n = match i {
+            0 => 1,
+            98 => (b'a' as u64) << 32 | 1 << 31 | 1,
+            _ => 0,
+        };
+        cyclic.extend_from_slice(&n.to_ne_bytes());
+    }
+    assert_queries_end(&Trie::read_from(&mut &cyclic[..]).unwrap());
+    let mut buf = vec![0u64; cyclic.len() / 8];
+    let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, cyclic.len()) };
+    bytes.copy_from_slice(&cyclic);
+    assert_queries_end(&Trie::from_bytes(bytes).unwrap());
+
+    // Corrupted data
+    let last = data.len() - 1;
+    data[last] ^= 0xFF;
+    File::create(&path).unwrap().write_all(&data).unwrap();
+    assert!(Trie::load(&path).is_err());
+}
+
+// Runs the queries which walk down a trie without a word to follow
+fn assert_queries_end(trie: &Trie) {
+    trie.len();
+    for id in 0..3 {
+        trie.get_word(id);
+    }
+    trie.iter().count();
+    trie.search_predictive("a").count();
+    trie.search_fuzzy("a", 2).count();
+    trie.search_wildcard("*").unwrap().count();
+    trie.search_regex(".*").unwrap().count();
+    assert!(trie == trie);
+    assert!(trie.is_subset(trie));
+    trie.union(trie);
+}
+
+#[test]
+fn build_empty() {
+    let trie = DoubleArrayBuilder::new().build(BinTreeBuilder::new().finish()).unwrap();
+    assert_eq!(0, trie.len());
+    assert!(!trie.contains("".bytes()));
+    assert!(!trie.contains("abc".bytes()));
+    assert_eq!(0, trie.iter().count());
+}
+
+#[test]
+fn load_malformed_index() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+    let mut data = Vec::new();
+    trie.write_to(&mut data).unwrap();
+
+    for len in 0..data.len() {
+        match Trie::read_from(&mut &data[..len]) {
+            Err(Error::Io(_)) => {}
+            Err(Error::Format(_)) => {}
+            _ => panic!("truncated index was accepted: len={}", len),
+        }
+    }
+
+    let mut unknown_version = data.clone();
+    unknown_version[4] = 0xFF;
+    match Trie::read_from(&mut &unknown_version[..]) {
+        Err(Error::Format(_)) => {}
+        _ => panic!("unknown format version was accepted"),
+    }
+
+    // The checksum can be recomputed for a broken index, so a cycle may come with any header.
+    // Queries must end on it whether or not the nodes in the cycle are terminal.
+    for is_terminal in [false, true].iter() {
+        let mut nodes = vec![0; 200];
+        nodes[0] = 1;
+        nodes[98] = (b'a' as u64) << 32 | (*is_terminal as u64) << 31 | 1;
+        let mut cyclic = Vec::new();
+        Trie::new(nodes, vec![]).write_to(&mut cyclic).unwrap();
+        assert_queries_end(&Trie::read_from(&mut &cyclic[..]).unwrap());
+        let mut buf = vec![0u64; cyclic.len() / 8];
+        let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, cyclic.len()) };
+        bytes.copy_from_slice(&cyclic);
+        let trie = Trie::from_bytes(bytes).unwrap();
+        assert_queries_end(&trie);
+        assert!(trie.verify().is_err());
+    }
+
+    // A slot whose label equals its index must not become a child of the nodes of base 0
+    let mut nodes = vec![0; 200];
+    nodes[0] = 1;
+    nodes[64] = 64 << 32;
+    nodes[98] = (b'a' as u64) << 32 | 1 << 31;
+    let mut data = Vec::new();
+    Trie::new(nodes, vec![]).write_to(&mut data).unwrap();
+    let loaded = Trie::read_from(&mut &data[..]).unwrap();
+    assert_eq!(vec![b"a".to_vec()],
+               loaded.iter().take(10).map(|(_, w)| w).collect::<Vec<_>>());
+    let mut buf = vec![0u64; data.len() / 8];
+    let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, data.len()) };
+    bytes.copy_from_slice(&data);
+    let loaded = Trie::from_bytes(bytes).unwrap();
+    assert_eq!(1, loaded.len());
+    assert_eq!(1, loaded.search_predictive("a".bytes()).take(10).count());
+    assert!(loaded == loaded);
+
+    // Sizes whose sum overflows on 32-bit targets
+    let mut huge = vec![0u64; 2];
+    let bytes = unsafe { slice::from_raw_parts_mut(huge.as_mut_ptr() as *mut u8, 16) };
+    bytes[0..4].copy_from_slice(&0xFFFF_FFF8u32.to_ne_bytes());
+    bytes[4..8].copy_from_slice(&0xFFFF_FFFCu32.to_ne_bytes());
+    assert!(Trie::from_bytes(bytes).is_err());
+
+    // Broken nodes must not make queries panic
+    let mut buf = vec![0u64; data.len() / 8 + 1];
+    let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, data.len()) };
+    for i in 48..data.len() {
+        bytes.copy_from_slice(&data);
+        bytes[i] = 0xFF;
+        if let Ok(trie) = Trie::from_bytes(bytes) {
+            for w in words().iter() {
+                trie.get_id(w.bytes());
+                trie.search_common_prefix(w.bytes()).count();
+            }
+        }
+    }
+}
+
+#[test]
+fn verify() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+    assert!(trie.verify().is_ok());
+    assert!(trie.verify_words(words().iter().map(|w| w.bytes())).is_ok());
+    assert!(trie.verify_words(words()[1..].iter().map(|w| w.bytes())).is_err());
+    assert!(trie.verify_words(words()[..6].iter().map(|w| w.bytes())).is_err());
+    assert!(trie.verify_words(vec!["abc", "b", "bb"].into_iter().map(|w| w.bytes())).is_err());
+
+    let empty = DoubleArrayBuilder::new().build(BinTreeBuilder::new().finish()).unwrap();
+    assert!(empty.verify().is_ok());
+    assert!(empty.verify_words(Vec::<&[u8]>::new()).is_ok());
+
+    // A broken index either fails to verify or is consistent as a trie of its own
+    let mut data = Vec::new();
+    trie.write_to(&mut data).unwrap();
+    let mut buf = vec![0u64; data.len() / 8 + 1];
+    let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, data.len()) };
+    // Every byte of the used slots, and a sample of the others
+    let offsets = (48..data.len())
+                      .filter(|i| {
+                          let chck = data.get((i - 48) / 8 * 8 + 52);
+                          chck.map_or(false, |c| *c != 0) || i % 61 == 0
+                      })
+                      .collect::<Vec<_>>();
+    let mut rejected = 0;
+    for &i in offsets.iter() {
+        for x in [0x01, 0x10, 0x80, 0xFF].iter() {
+            bytes.copy_from_slice(&data);
+            bytes[i] ^= *x;
+            let broken = match Trie::from_bytes(bytes) {
+                Err(_) => continue,
+                Ok(broken) => broken,
+            };
+            if broken.verify().is_err() {
+                rejected += 1;
+                continue;
+            }
+            let words = broken.iter().collect::<Vec<_>>();
+            assert_eq!(broken.len(), words.len());
+            for (i, &(id, ref w)) in words.iter().enumerate() {
+                assert_eq!(i as u32, id);
+                assert_eq!(Some(id), broken.get_id(w));
+                assert_eq!(Some(w.clone()), broken.get_word(id));
+            }
+        }
+    }
+    assert!(rejected > 0);
+}
+
+#[test]
+fn build_unsorted() {
+    let sorted = words()
+                     .iter()
+                     .fold(BinTreeBuilder::new(), |mut b, w| {
+                         b.insert(w.bytes()).ok().unwrap();
+                         b
+                     })
+                     .finish();
+    let unsorted = words()
+                       .iter()
+                       .rev()
+                       .fold(BinTreeBuilder::unsorted(), |mut b, w| {
+                           b.insert(w.bytes()).ok().unwrap();
+                           b
+                       })
+                       .finish();
+
+    // Both builders produce the same minimal graph
+    let mut expected = Vec::new();
+    DoubleArrayBuilder::new().build(sorted).unwrap().write_to(&mut expected).unwrap();
+    let mut actual = Vec::new();
+    DoubleArrayBuilder::new().build(unsorted).unwrap().write_to(&mut actual).unwrap();
+    assert_eq!(expected, actual);
+}
+
+#[test]
+fn build_large_alphabet() {
+    let mut words = Vec::new();
+    for i in 1..0x100usize {
+        for j in (1..0x100usize).filter(|j| (i * j) % 3 == 0) {
+            words.push(vec![i as u8, j as u8, ((i + j) % 0xFF + 1) as u8]);
+        }
+    }
+    let mut b = BinTreeBuilder::new();
+    for w in words.iter() {
+        b.insert(w).unwrap();
+    }
+    let trie = DoubleArrayBuilder::new().build(b.finish()).unwrap();
+    assert_eq!(words.len(), trie.len());
+    for (i, w) in words.iter().enumerate() {
+        assert_eq!(Some(i as u32), trie.get_id(w));
+    }
+    assert!(trie.fill_rate() > 0.5 && trie.fill_rate() <= 1.0);
+}
+
+#[test]
+fn build_parallel() {
+    let mut generated = Vec::new();
+    for i in 0..3000 {
+        generated.push(format!("{}{}", (b'a' + (i % 7) as u8) as char, i * 7919 % 10007));
+    }
+    generated.sort();
+    let word_sets = vec![words().iter().map(|w| w.to_string()).collect(),
+                         generated,
+                         vec!["abc".to_string()],
+                         vec![]];
+
+    for words in word_sets {
+        let build = |builder: DoubleArrayBuilder| {
+            let mut b = BinTreeBuilder::new();
+            for w in words.iter() {
+                b.insert(w.bytes()).unwrap();
+            }
+            builder.build(b.finish()).unwrap()
+        };
+        let expected = build(DoubleArrayBuilder::new());
+        for threads in 1..5 {
+            let trie = build(DoubleArrayBuilder::with_threads(threads));
+            assert_eq!(words.len(), trie.len());
+            assert_eq!(expected.iter().collect::<Vec<_>>(),
+                       trie.iter().collect::<Vec<_>>());
+            for (i, w) in words.iter().enumerate() {
+                assert_eq!(Some(i as u32), trie.get_id(w.bytes()));
+                assert_eq!(Some(w.clone().into_bytes()), trie.get_word(i as u32));
+            }
+            assert!(!trie.contains("ab".bytes()));
+        }
+    }
+}
+
+#[test]
+fn search_fuzzy() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    assert_eq!(0, trie.search_fuzzy("hoge".bytes(), 1).count());
+
+    assert_eq!(vec![(3, b"car".to_vec(), 1),
+                    (4, b"cd".to_vec(), 1),
+                    (5, b"cddr".to_vec(), 1),
+                    (6, b"cdr".to_vec(), 0)],
+               trie.search_fuzzy("cdr".bytes(), 1).collect::<Vec<_>>());
+
+    assert_eq!(0, trie.search_fuzzy("acr".bytes(), 1).count());
+    assert_eq!(vec![(3, b"car".to_vec(), 1)],
+               trie.search_fuzzy_with_transposition("acr".bytes(), 1).collect::<Vec<_>>());
+}
+
+#[test]
+fn search_wildcard() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+    let search = |pattern| trie.search_wildcard(pattern).unwrap().collect::<Vec<_>>();
+
+    assert_eq!(0, search("c?x*").len());
+    assert_eq!(7, search("*").len());
+    assert_eq!(vec![(4, b"cd".to_vec())], search("c?"));
+    assert_eq!(vec![(3, b"car".to_vec()), (5, b"cddr".to_vec()), (6, b"cdr".to_vec())],
+               search("c**r"));
+    assert_eq!(vec![(4, b"cd".to_vec()), (5, b"cddr".to_vec()), (6, b"cdr".to_vec())],
+               search("?d*"));
+    assert_eq!(vec![(0, b"abc".to_vec()), (1, b"b".to_vec()), (2, b"bbb".to_vec())],
+               search("[a-b]*"));
+    assert_eq!(vec![(0, b"abc".to_vec()), (2, b"bbb".to_vec())], search("[!c]??"));
+    assert_eq!(vec![(1, b"b".to_vec())], search("\\b"));
+
+    for pattern in ["[a-", "ab\\", "[c-a]", "[]"].iter() {
+        match trie.search_wildcard(pattern) {
+            Err(Error::Pattern(_)) => {}
+            _ => panic!("pattern {:?} should be rejected", pattern),
+        }
+    }
+}
+
+#[test]
+fn search_regex() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+    let search = |pattern| trie.search_regex(pattern).unwrap().collect::<Vec<_>>();
+
+    assert_eq!(0, search("x|c{2}").len());
+    assert_eq!(7, search(".*").len());
+    assert_eq!(vec![(4, b"cd".to_vec())], search("^c.?$"));
+    assert_eq!(vec![(1, b"b".to_vec()), (2, b"bbb".to_vec())], search("b+"));
+    assert_eq!(vec![(0, b"abc".to_vec()), (2, b"bbb".to_vec())],
+               search("[a-c]{3}"));
+    assert_eq!(vec![(3, b"car".to_vec()), (5, b"cddr".to_vec()), (6, b"cdr".to_vec())],
+               search("c(a|d+)r"));
+    assert_eq!(vec![(0, b"abc".to_vec()), (1, b"b".to_vec()), (2, b"bbb".to_vec())],
+               search("(?:\\w\\w)*[^r]"));
+
+    for pattern in ["(ab", "ab)", "*a", "a{3,1}", "[a-", "\\q"].iter() {
+        match trie.search_regex(pattern) {
+            Err(Error::Pattern(_)) => {}
+            _ => panic!("pattern {:?} should be rejected", pattern),
+        }
+    }
+}
+
+// Accepts the words of at most the given length
+struct MaxLength(usize);
+
+impl Automaton for MaxLength {
+    type State = usize;
+    fn start(&self) -> usize {
+        0
+    }
+    fn step(&self, len: &usize, _: u8) -> usize {
+        len + 1
+    }
+    fn is_match(&self, _: &usize) -> bool {
+        true
+    }
+    fn can_match(&self, len: &usize) -> bool {
+        *len <= self.0
+    }
+}
+
+#[test]
+fn search() {
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    assert_eq!(0, trie.search(MaxLength(0)).count());
+    assert_eq!(vec![(1, b"b".to_vec()), (4, b"cd".to_vec())],
+               trie.search(MaxLength(2)).collect::<Vec<_>>());
+    assert_eq!(7, trie.search(MaxLength(4)).count());
+
+    let regex = Regex::new("c.*r").unwrap();
+    assert_eq!(vec![(3, b"car".to_vec()), (5, b"cddr".to_vec()), (6, b"cdr".to_vec())],
+               trie.search(&regex).collect::<Vec<_>>());
+    let pattern = Pattern::parse("?b*").unwrap();
+    assert_eq!(vec![(0, b"abc".to_vec()), (2, b"bbb".to_vec())],
+               trie.search(&pattern).collect::<Vec<_>>());
+}
+
+#[test]
+fn non_utf8_words() {
+    let words: [&[u8]; 4] = [b"\x01\xff", b"\x80", b"\x80\x80", b"\xfe"];
+    let mut builder = BinTreeBuilder::new();
+    for w in words.iter() {
+        builder.insert(*w).unwrap();
+    }
+    let trie = builder.finish();
+    let trie = DoubleArrayBuilder::new().build(trie).unwrap();
+
+    assert_eq!(4, trie.len());
+    assert_eq!(Some(0), trie.get_id([1, 255].iter()));
+    assert_eq!(Some(2), trie.get_id(vec![128, 128]));
+    assert_eq!(Some(3), trie.get_id(b"\xfe"));
+    assert_eq!(Some(3), trie.get_id(WordIter(Some(254))));
+    assert_eq!(None, trie.get_id(b"\x01"));
+    assert!(trie.contains(WordIter((0..2).map(|_| 128))));
+    assert!(!trie.contains(b""));
+    assert_eq!(vec![(1, 1), (2, 2)],
+               trie.search_common_prefix(b"\x80\x80\x80").collect::<Vec<_>>());
+    assert_eq!(Some(b"\x80\x80".to_vec()), trie.get_word(2));
+}
+
+#[test]
+fn search_str() {
+    let mut words: Vec<&[u8]> = vec!["café".as_bytes(),
+                                     "cafés".as_bytes(),
+                                     "caffè".as_bytes(),
+                                     "日本".as_bytes(),
+                                     "日本語".as_bytes(),
+                                     b"\xe6\x97"]; // a truncated "日"
+    words.sort();
+    let mut builder = BinTreeBuilder::new();
+    for w in words.iter() {
+        builder.insert(*w).unwrap();
+    }
+    let trie = DoubleArrayBuilder::new().build(builder.finish()).unwrap();
+
+    assert_eq!(vec![(4, "日本"), (5, "日本語")],
+               trie.search_common_prefix_str("日本語です").collect::<Vec<_>>());
+    assert_eq!(3, trie.search_common_prefix("日本語です".bytes()).count());
+
+    assert_eq!(vec![(0, "caffè".to_owned()), (1, "café".to_owned()), (2, "cafés".to_owned())],
+               trie.search_predictive_str("caf").collect::<Vec<_>>());
+    assert_eq!(5, trie.search_predictive_str("").count());
+
+    assert_eq!(0, trie.search_fuzzy("cafe".bytes(), 1).count());
+    assert_eq!(vec![(1, "café".to_owned(), 1)],
+               trie.search_fuzzy_str("cafe", 1).collect::<Vec<_>>());
+    assert_eq!(vec![(4, "日本".to_owned(), 1), (5, "日本語".to_owned(), 1)],
+               trie.search_fuzzy_str("日本人", 1).collect::<Vec<_>>());
+
+    assert_eq!(0, trie.search_fuzzy_str("本日", 1).count());
+    assert_eq!(vec![(4, "日本".to_owned(), 1)],
+               trie.search_fuzzy_str_with_transposition("本日", 1).collect::<Vec<_>>());
+}
+
+#[test]
+fn stats() {
+    let mut b = BinTreeBuilder::new();
+    for w in words().iter() {
+        b.insert(w.bytes()).unwrap();
+    }
+    let trie = DoubleArrayBuilder::new().build(b.finish()).unwrap();
+    let stats = trie.stats();
+    assert_eq!(7, stats.key_count);
+    assert_eq!(19, stats.key_bytes);
+    assert_eq!(stats.used_slot_count, stats.node_types.iter().sum());
+    assert_eq!(stats.node_count + 1,
+               stats.used_slot_count + stats.encoded_child_count);
+    assert_eq!(trie.fill_rate(), stats.fill_rate());
+    assert_eq!(0, stats.ext_count);
+
+    let empty = DoubleArrayBuilder::new().build(BinTreeBuilder::new().finish()).unwrap();
+    assert_eq!(0, empty.stats().key_count);
+    assert_eq!(0, empty.stats().node_count);
+
+    let no_slots = Trie::new(vec![], vec![]);
+    assert_eq!(0, no_slots.stats().slot_count);
+    assert_eq!(0, no_slots.stats().used_slot_count);
+
+    // 'a' leads back to the root's base
+    let mut nodes = vec![0; 0x200];
+    nodes[0] = 1;
+    nodes[1 + b'a' as usize] = (b'a' as u64) << 32 | 1 << 31 | 1;
+    let cyclic = Trie::new(nodes, vec![]);
+    assert_eq!(2, cyclic.stats().used_slot_count);
+    assert!(cyclic.verify().is_err());
+}
+
+#[test]
+fn to_dot() {
+    let mut b = BinTreeBuilder::new();
+    for w in words().iter() {
+        b.insert(w.bytes()).unwrap();
+    }
+    let trie = DoubleArrayBuilder::new().build(b.finish()).unwrap();
+    let mut dot = Vec::new();
+    trie.to_dot(&mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+
+    assert!(dot.starts_with("digraph dawg {\n"));
+    assert!(dot.ends_with("}\n"));
+    assert!(dot.contains(" root\\nbase="));
+    assert_eq!(13, dot.matches(" -> ").count());
+    assert_eq!(2, dot.matches("style=dashed").count()); // "b" of "abc" and "bbb"
+    assert_eq!(6, dot.matches("peripheries=2").count());
+}
+
+#[test]
+fn set_operations() {
+    let build = |words: &[&str]| {
+        let mut builder = BinTreeBuilder::new();
+        for w in words.iter() {
+            builder.insert(w.bytes()).unwrap();
+        }
+        DoubleArrayBuilder::new().build(builder.finish()).unwrap()
+    };
+    let a = build(&words());
+    let b = build(&["b", "bb", "cd", "cdr", "x"]);
+    let words_of = |trie: dawg::binary_tree::Trie| {
+        trie.iter().map(|(_, w)| String::from_utf8(w).unwrap()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(vec!["abc", "b", "bb", "bbb", "car", "cd", "cddr", "cdr", "x"],
+               words_of(a.union(&b)));
+    assert_eq!(vec!["b", "cd", "cdr"], words_of(a.intersection(&b)));
+    assert_eq!(vec!["abc", "bbb", "car", "cddr"], words_of(a.difference(&b)));
+    assert_eq!(vec!["bb", "x"], words_of(b.difference(&a)));
+    assert_eq!(0, a.difference(&a).len());
+
+    assert!(a.is_subset(&a));
+    assert!(!a.is_subset(&b));
+    assert!(build(&["b", "cd"]).is_subset(&b));
+    assert!(!build(&["c"]).is_subset(&b));
+
+    assert!(a == build(&words()));
+    assert!(a != b);
+    assert!(build(&["b", "cd"]) != build(&["b", "cdr"]));
+    assert!(a.union(&b) == b.union(&a));
+}
+
+#[test]
+fn share_between_threads() {
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    let trie = words()
+                   .iter()
+                   .fold(BinTreeBuilder::new(), |mut b, w| {
+                       b.insert(w.bytes()).ok().unwrap();
+                       b
+                   })
+                   .finish();
+    let trie = Arc::new(DoubleArrayBuilder::new().build(trie).unwrap());
+    let workers = (0..4)
+                      .map(|_| {
+                          let trie = trie.clone();
+                          thread::spawn(move || {
+                              assert_eq!(vec![(4, 2), (5, 4)],
+                                         trie.search_common_prefix("cddrr".bytes())
+                                             .collect::<Vec<_>>());
+                              assert_eq!(Some(b"abc".to_vec()), trie.get_word(0));
+                              trie.iter().count()
+                          })
+                      })
+                      .collect::<Vec<_>>();
+    for worker in workers {
+        assert_eq!(7, worker.join().unwrap());
+    }
+
+    // A trie which borrows its arrays is shareable as well
+    let mut data = Vec::new();
+    trie.write_to(&mut data).unwrap();
+    let mut buf = vec![0u64; data.len() / 8 + 1];
+    let bytes = unsafe { slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, data.len()) };
+    bytes.copy_from_slice(&data);
+    let borrowed = Trie::from_bytes(bytes).unwrap();
+    assert_send_sync(&borrowed);
+    assert_eq!(7, borrowed.iter().count());
 }
 
 fn words() -> [&'static str; 7] {
     ["abc", "b", "bbb", "car", "cd", "cddr", "cdr"]
 }
+
+fn read_le_u32(bytes: &[u8]) -> u32 {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(&bytes[0..4]);
+    u32::from_le_bytes(buf)
+}
+
+fn read_le_u64(bytes: &[u8]) -> u64 {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&bytes[0..8]);
+    u64::from_le_bytes(buf)
+}
@@ -0,0 +1,80 @@
+// Copyright (c) 2015 Takeru Ohta <phjgt308@gmail.com>
+//
+// This software is released under the MIT License,
+// see the LICENSE file at the top-level directory.
+
+extern crate dawg;
+
+use std::env;
+use dawg::map::Builder;
+use dawg::map::DawgMap;
+
+#[test]
+fn get() {
+    let map = build();
+    assert_eq!(entries().len(), map.len());
+    for &(w, v) in entries().iter() {
+        assert_eq!(Some(v), map.get(w.bytes()));
+    }
+    assert_eq!(None, map.get("hoge".bytes()));
+    assert_eq!(None, map.get("ca".bytes()));
+}
+
+#[test]
+fn overwrite() {
+    let mut b = Builder::new();
+    b.insert("abc".bytes(), 1u32).unwrap();
+    b.insert("abc".bytes(), 2).unwrap();
+    b.insert("b".bytes(), 3).unwrap();
+    assert!(b.insert("a".bytes(), 4).is_err());
+    let map = b.finish().unwrap();
+
+    assert_eq!(2, map.len());
+    assert_eq!(Some(2), map.get("abc".bytes()));
+    assert_eq!(Some(3), map.get("b".bytes()));
+}
+
+#[test]
+fn insert_unsorted() {
+    let mut b = Builder::new();
+    b.insert("abc".bytes(), 1u32).unwrap();
+    assert!(b.insert("ab".bytes(), 2).is_err());
+    b.insert("abd".bytes(), 3).unwrap();
+    let map = b.finish().unwrap();
+
+    assert_eq!(2, map.len());
+    assert_eq!(None, map.get("ab".bytes()));
+    assert_eq!(Some(1), map.get("abc".bytes()));
+    assert_eq!(Some(3), map.get("abd".bytes()));
+}
+
+#[test]
+fn save_and_load() {
+    let map = build();
+    let path = env::temp_dir().join("dawg_test_map_save_and_load.idx");
+    map.save(&path).unwrap();
+
+    let loaded = DawgMap::<u32>::load(&path).unwrap();
+    assert_eq!(map.values(), loaded.values());
+    for &(w, v) in entries().iter() {
+        assert_eq!(Some(v), loaded.get(w.bytes()));
+    }
+
+    // The value size is checked
+    assert!(DawgMap::<u64>::load(&path).is_err());
+}
+
+fn build() -> DawgMap<u32> {
+    entries()
+        .iter()
+        .fold(Builder::new(), |mut b, &(w, v)| {
+            b.insert(w.bytes(), v).ok().unwrap();
+            b
+        })
+        .finish()
+        .unwrap()
+}
+
+fn entries() -> [(&'static str, u32); 7] {
+    [("abc", 10), ("b", 20), ("bbb", 30), ("car", 0), ("cd", 50), ("cddr", 60), ("cdr", 70)]
+}